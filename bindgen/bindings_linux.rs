@@ -1,7 +1,9 @@
 pub const ENOENT: u32 = 2;
 pub const ENOMEM: u32 = 12;
+pub const EACCES: u32 = 13;
 pub const EINVAL: u32 = 22;
 pub const ERANGE: u32 = 34;
+pub const ENODATA: u32 = 61;
 pub const ENOTSUP: u32 = 95;
 pub const ACL_READ: u32 = 4;
 pub const ACL_WRITE: u32 = 2;
@@ -190,6 +192,52 @@ extern "C" {
 extern "C" {
     pub fn acl_get_perm(permset_d: acl_permset_t, perm: acl_perm_t) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn setxattr(
+        __path: *const ::std::os::raw::c_char,
+        __name: *const ::std::os::raw::c_char,
+        __value: *const ::std::os::raw::c_void,
+        __size: usize,
+        __flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn fsetxattr(
+        __fd: ::std::os::raw::c_int,
+        __name: *const ::std::os::raw::c_char,
+        __value: *const ::std::os::raw::c_void,
+        __size: usize,
+        __flags: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn getxattr(
+        __path: *const ::std::os::raw::c_char,
+        __name: *const ::std::os::raw::c_char,
+        __value: *mut ::std::os::raw::c_void,
+        __size: usize,
+    ) -> isize;
+}
+extern "C" {
+    pub fn fgetxattr(
+        __fd: ::std::os::raw::c_int,
+        __name: *const ::std::os::raw::c_char,
+        __value: *mut ::std::os::raw::c_void,
+        __size: usize,
+    ) -> isize;
+}
+extern "C" {
+    pub fn removexattr(
+        __path: *const ::std::os::raw::c_char,
+        __name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn fremovexattr(
+        __fd: ::std::os::raw::c_int,
+        __name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct group {
@@ -8,6 +8,8 @@
 #elif __linux__
 // Linux supplies non-standard ACL extensions in a different header.
 # include <acl/libacl.h>
+// Used by the `xattr_backend` feature.
+# include <sys/xattr.h>
 #endif
 #include <grp.h>
 #include <pwd.h>
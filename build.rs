@@ -17,8 +17,9 @@ fn main() {
     let out_path = Path::new(&out_dir).join("bindings.rs");
     let wrapper = "bindgen/wrapper.h";
 
-    // Tell cargo to tell rustc to link libacl.so, only on Linux.
-    #[cfg(target_os = "linux")]
+    // Tell cargo to tell rustc to link libacl.so, only on Linux. The
+    // `xattr_backend` feature does not use libacl.
+    #[cfg(all(target_os = "linux", not(feature = "xattr_backend")))]
     println!("cargo:rustc-link-lib=acl");
 
     // Tell cargo to invalidate the built crate whenever the wrapper changes
@@ -63,6 +64,8 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
         "pathconf",
         #[cfg(target_os = "freebsd")]
         "lpathconf",
+        #[cfg(target_os = "linux")]
+        "f?(get|set|remove)xattr",
     ];
     let vars = [
         "ACL_.*",
@@ -72,6 +75,10 @@ fn bindgen_bindings(wrapper: &str, out_path: &Path) {
         "EINVAL",
         "ENOMEM",
         "ERANGE",
+        #[cfg(target_os = "linux")]
+        "EACCES",
+        #[cfg(target_os = "linux")]
+        "ENODATA",
         #[cfg(target_os = "macos")]
         "O_SYMLINK",
         "ID_TYPE_UID",
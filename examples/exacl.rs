@@ -10,7 +10,14 @@
 //! use the -s option.
 //!
 //! To get/set the default ACL (on Linux), use the -d option.
+//!
+//! To read the ACL with the file's path, owner, group and mode as a JSON
+//! document, or to set them from one:
+//!     exacl -f document myfile
+//!     exacl -f document --set myfile
 
+#[cfg(feature = "serde")]
+use exacl::AclDocument;
 use exacl::{getfacl, setfacl, AclEntry, AclOption};
 use std::io;
 use std::path::{Path, PathBuf};
@@ -38,6 +45,10 @@ struct Opt {
     #[arg(short = 's', long)]
     symlink: bool,
 
+    /// Use numeric user and group IDs.
+    #[arg(short = 'n', long)]
+    numeric: bool,
+
     /// Format of input or output.
     #[arg(value_enum, short = 'f', long, default_value = "json")]
     format: Format,
@@ -52,6 +63,7 @@ struct Opt {
 enum Format {
     Json,
     Std,
+    Document,
 }
 
 const EXIT_SUCCESS: i32 = 0;
@@ -72,6 +84,9 @@ fn main() {
     if opt.symlink {
         options |= AclOption::SYMLINK_ACL;
     }
+    if opt.numeric {
+        options |= AclOption::NUMERIC_IDS;
+    }
 
     let exit_code = if opt.set {
         set_acl(&opt.files, options, opt.format)
@@ -94,6 +109,10 @@ fn get_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
 }
 
 fn set_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
+    if matches!(format, Format::Document) {
+        return set_document(paths, options);
+    }
+
     let entries = match read_input(format) {
         Some(entries) => entries,
         None => return EXIT_FAILURE,
@@ -108,6 +127,10 @@ fn set_acl(paths: &[PathBuf], options: AclOption, format: Format) -> i32 {
 }
 
 fn dump_acl(path: &Path, options: AclOption, format: Format) -> io::Result<()> {
+    if matches!(format, Format::Document) {
+        return dump_document(path, options);
+    }
+
     let entries = getfacl(path, options)?;
 
     match format {
@@ -121,11 +144,52 @@ fn dump_acl(path: &Path, options: AclOption, format: Format) -> io::Result<()> {
             panic!("serde not supported");
         }
         Format::Std => exacl::to_writer(io::stdout(), &entries)?,
+        Format::Document => unreachable!(),
     };
 
     Ok(())
 }
 
+#[cfg(feature = "serde")]
+fn dump_document(path: &Path, options: AclOption) -> io::Result<()> {
+    let doc = AclDocument::read(path, options)?;
+    serde_json::to_writer(io::stdout(), &doc)?;
+    println!(); // add newline
+
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump_document(_path: &Path, _options: AclOption) -> io::Result<()> {
+    panic!("serde not supported");
+}
+
+#[cfg(feature = "serde")]
+fn set_document(paths: &[PathBuf], options: AclOption) -> i32 {
+    let reader = io::BufReader::new(io::stdin());
+    let doc: AclDocument = match serde_json::from_reader(reader) {
+        Ok(doc) => doc,
+        Err(err) => {
+            eprintln!("JSON parser error: {err}");
+            return EXIT_FAILURE;
+        }
+    };
+
+    for path in paths {
+        if let Err(err) = doc.apply(path, options) {
+            eprintln!("{err}");
+            return EXIT_FAILURE;
+        }
+    }
+
+    EXIT_SUCCESS
+}
+
+#[cfg(not(feature = "serde"))]
+fn set_document(_paths: &[PathBuf], _options: AclOption) -> i32 {
+    panic!("serde not supported");
+}
+
 fn read_input(format: Format) -> Option<Vec<AclEntry>> {
     let reader = io::BufReader::new(io::stdin());
 
@@ -151,6 +215,7 @@ fn read_input(format: Format) -> Option<Vec<AclEntry>> {
                 return None;
             }
         },
+        Format::Document => unreachable!(),
     };
 
     Some(entries)
@@ -0,0 +1,317 @@
+//! Implements the Posix.1e access check algorithm and effective permissions.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::error::Error;
+use crate::failx::fail;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::resolver::{NssResolver, PrincipalResolver};
+
+use std::io;
+
+/// User credentials used to evaluate access.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Credentials {
+    /// Effective user ID.
+    pub uid: u32,
+
+    /// Effective (primary) group ID.
+    pub gid: u32,
+
+    /// Supplementary group IDs.
+    pub groups: Vec<u32>,
+}
+
+impl Credentials {
+    /// Construct credentials from a uid, primary gid and supplementary gids.
+    #[must_use]
+    pub fn new(uid: u32, gid: u32, groups: &[u32]) -> Credentials {
+        Credentials {
+            uid,
+            gid,
+            groups: groups.to_vec(),
+        }
+    }
+
+    /// Return true if the credentials include the given group.
+    fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/// Return true if an access ACL grants the `requested` permissions to a user.
+///
+/// `entries` is an ACL as returned by [`getfacl`](crate::getfacl); default
+/// entries are ignored. `owner_uid` and `owner_gid` are the file's owner and
+/// group. This implements the Posix.1e algorithm used by the kernel:
+///
+/// 1. If the user owns the file, the owner entry applies.
+/// 2. Otherwise, if a named user entry matches, it applies, limited by the
+///    mask.
+/// 3. Otherwise, if the owning group or any named group entry matches one of
+///    the user's groups, access is granted if a single matching entry
+///    contains all of the requested permissions, limited by the mask. The
+///    permissions of different group entries are not combined.
+/// 4. Otherwise, the other entry applies.
+///
+/// User and group names are resolved with
+/// [`NssResolver`](crate::NssResolver). Privileges like `CAP_DAC_OVERRIDE`
+/// are not considered.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a user or group name can't be resolved, or if
+/// an entry denies access (only allow entries are supported).
+pub fn check_access(
+    entries: &[AclEntry],
+    owner_uid: u32,
+    owner_gid: u32,
+    creds: &Credentials,
+    requested: Perm,
+) -> io::Result<bool> {
+    check_access_with(
+        entries,
+        owner_uid,
+        owner_gid,
+        creds,
+        requested,
+        &NssResolver,
+    )
+}
+
+/// Return true if an access ACL grants the `requested` permissions to a
+/// user, using `resolver` to convert user/group names to uid/gid.
+///
+/// Works like [`check_access`], which uses
+/// [`NssResolver`](crate::NssResolver).
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a user or group name can't be resolved, or if
+/// an entry denies access (only allow entries are supported).
+pub fn check_access_with<R>(
+    entries: &[AclEntry],
+    owner_uid: u32,
+    owner_gid: u32,
+    creds: &Credentials,
+    requested: Perm,
+    resolver: &R,
+) -> io::Result<bool>
+where
+    R: PrincipalResolver + ?Sized,
+{
+    let mut owner = None;
+    let mut user = None;
+    let mut group_matched = false;
+    let mut group_covers = false;
+    let mut mask = None;
+    let mut other = Perm::empty();
+
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) {
+            continue;
+        }
+
+        if !entry.allow {
+            return fail(Error::NotSupported(
+                "check_access only supports allow entries".to_string(),
+            ));
+        }
+
+        let in_group = match entry.kind {
+            AclEntryKind::Mask => {
+                mask = Some(entry.perms);
+                false
+            }
+            AclEntryKind::Other => {
+                other = entry.perms;
+                false
+            }
+            AclEntryKind::User | AclEntryKind::Group => match entry.qualifier(&resolver)? {
+                Qualifier::UserObj if creds.uid == owner_uid => {
+                    owner = Some(entry.perms);
+                    false
+                }
+                Qualifier::User(uid) if creds.uid == uid => {
+                    user = Some(entry.perms);
+                    false
+                }
+                Qualifier::GroupObj => creds.in_group(owner_gid),
+                Qualifier::Group(gid) => creds.in_group(gid),
+                _ => false,
+            },
+            #[cfg(target_os = "freebsd")]
+            AclEntryKind::Everyone => {
+                return fail(Error::NotSupported(
+                    "check_access only supports Posix.1e ACL's".to_string(),
+                ));
+            }
+            AclEntryKind::Unknown => false,
+        };
+
+        if in_group {
+            group_matched = true;
+            group_covers |= entry.perms.contains(requested);
+        }
+    }
+
+    let mask = mask.unwrap_or_else(Perm::all);
+    let granted = match (owner, user) {
+        (Some(perms), _) => perms.contains(requested),
+        (None, Some(perms)) => (perms & mask).contains(requested),
+        // The kernel picks the first group entry that contains the requested
+        // permissions, then applies the mask to it.
+        (None, None) if group_matched => group_covers && mask.contains(requested),
+        (None, None) => other.contains(requested),
+    };
+
+    Ok(granted)
+}
+
+/// Return each entry paired with its effective permissions.
+///
+/// The permissions of the group class entries (named users, the owning group
+/// and named groups) are limited by the mask entry, like the `#effective:`
+/// annotation in GNU `getfacl`. Other entries are unchanged. The access ACL
+/// and the default ACL (entries with [`Flag::DEFAULT`]) of a unified list each
+/// use their own mask. If there is no mask, the permissions are unchanged.
+#[must_use]
+pub fn effective_perms(entries: &[AclEntry]) -> Vec<(&AclEntry, Perm)> {
+    let find_mask = |default: bool| {
+        entries
+            .iter()
+            .find(|e| e.kind == AclEntryKind::Mask && e.flags.contains(Flag::DEFAULT) == default)
+            .map(|e| e.perms)
+    };
+
+    let access_mask = find_mask(false);
+    let default_mask = find_mask(true);
+
+    entries
+        .iter()
+        .map(|entry| {
+            let mask = if entry.flags.contains(Flag::DEFAULT) {
+                default_mask
+            } else {
+                access_mask
+            };
+
+            let in_group_class = match entry.kind {
+                AclEntryKind::User => !entry.name.is_empty(),
+                AclEntryKind::Group => true,
+                _ => false,
+            };
+
+            match mask {
+                Some(mask) if in_group_class => (entry, entry.perms & mask),
+                _ => (entry, entry.perms),
+            }
+        })
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod access_tests {
+    use super::*;
+
+    fn entries() -> Vec<AclEntry> {
+        crate::from_str(
+            "u::rwx
+            u:1234:rwx
+            g::r
+            g:2000:w
+            g:2001:x
+            m::rw
+            o::r
+            d:u:5000:rwx",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_access() {
+        let entries = entries();
+        let check = |uid, gid, groups: &[u32], perms| {
+            check_access(
+                &entries,
+                1000,
+                100,
+                &Credentials::new(uid, gid, groups),
+                perms,
+            )
+            .unwrap()
+        };
+
+        // Owner is not limited by mask.
+        assert!(check(1000, 3000, &[], Perm::all()));
+
+        // Named user is limited by mask.
+        assert!(check(1234, 100, &[], Perm::READ | Perm::WRITE));
+        assert!(!check(1234, 100, &[], Perm::EXECUTE));
+
+        // Owning group via primary or supplementary group.
+        assert!(check(1, 100, &[], Perm::READ));
+        assert!(check(1, 3000, &[100], Perm::READ));
+        assert!(!check(1, 3000, &[100], Perm::WRITE));
+
+        // Each matching group entry is checked on its own, limited by mask.
+        assert!(check(1, 2000, &[2001, 100], Perm::READ));
+        assert!(check(1, 2000, &[2001, 100], Perm::WRITE));
+        assert!(!check(1, 2000, &[2001, 100], Perm::READ | Perm::WRITE));
+        assert!(!check(1, 2001, &[], Perm::EXECUTE));
+
+        // A matching group entry hides the other entry.
+        assert!(!check(1, 2001, &[], Perm::READ));
+
+        // Default entries are ignored.
+        assert!(check(5000, 3000, &[], Perm::READ));
+        assert!(!check(5000, 3000, &[], Perm::WRITE));
+    }
+
+    #[test]
+    fn test_check_access_no_mask() {
+        let entries = crate::from_mode(0o754);
+        let creds = Credentials::new(1, 100, &[]);
+        let check = |perms| check_access(&entries, 1000, 100, &creds, perms).unwrap();
+
+        assert!(check(Perm::READ | Perm::EXECUTE));
+        assert!(!check(Perm::WRITE));
+    }
+
+    #[test]
+    fn test_effective_perms() {
+        let entries = crate::from_str(
+            "u::rwx
+            u:1234:rwx
+            g::rw
+            m::r
+            o::rwx
+            d:u::rwx
+            d:g:2000:rwx
+            d:m::rx",
+        )
+        .unwrap();
+
+        let perms = effective_perms(&entries)
+            .into_iter()
+            .map(|(_, perms)| perms)
+            .collect::<Vec<Perm>>();
+
+        assert_eq!(
+            perms,
+            vec![
+                Perm::all(),
+                Perm::READ,
+                Perm::READ,
+                Perm::READ,
+                Perm::all(),
+                Perm::all(),
+                Perm::READ | Perm::EXECUTE,
+                Perm::READ | Perm::EXECUTE,
+            ]
+        );
+    }
+}
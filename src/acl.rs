@@ -3,16 +3,20 @@
 use crate::aclentry::AclEntry;
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::aclentry::AclEntryKind;
-use crate::failx::{fail_custom, path_err};
+use crate::error::Error;
+use crate::failx::{context_err, fail, fd_err, path_err};
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::flag::Flag;
 #[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use crate::perm::Perm;
+use crate::resolver::PrincipalResolver;
 use crate::util::*;
 
 use bitflags::bitflags;
 use scopeguard::{self, ScopeGuard};
+use std::fs::File;
 use std::io;
+use std::os::unix::io::{AsRawFd, BorrowedFd};
 use std::path::Path;
 
 bitflags! {
@@ -28,6 +32,11 @@ bitflags! {
         /// Get/set the ACL of the symlink itself (macOS only).
         const SYMLINK_ACL = 0b0100;
 
+        /// Use decimal uid/gid instead of user/group names, like
+        /// `getfacl -n`. When getting an ACL, names are not looked up. When
+        /// setting an ACL, user and group names must be decimal uid/gid.
+        const NUMERIC_IDS = 0b1000;
+
         /// Ignore expected error when using DEFAULT_ACL on a file.
         #[doc(hidden)]
         const IGNORE_EXPECTED_FILE_ERR = 0b10000;
@@ -112,9 +121,8 @@ impl Acl {
                 return Ok(());
             }
 
-            return fail_custom(&format!(
-                "File {path:?}: Non-directory does not have default ACL"
-            ));
+            let err = Error::NotSupported("Non-directory does not have default ACL".to_string());
+            return Err(path_err(path, &err.into()));
         }
 
         if let Err(err) = xacl_set_file(path, self.acl, symlink_acl, default_acl) {
@@ -124,9 +132,64 @@ impl Acl {
         Ok(())
     }
 
+    /// Read ACL for an open file descriptor.
+    ///
+    /// The `SYMLINK_ACL` option is ignored; the ACL belongs to whatever file
+    /// the descriptor refers to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn read_fd(fd: BorrowedFd, options: AclOption) -> io::Result<Acl> {
+        let default_acl = options.contains(AclOption::DEFAULT_ACL);
+
+        let result = xacl_get_fd(fd.as_raw_fd(), default_acl);
+        match result {
+            Ok(acl) => Ok(Acl::new(acl, default_acl)),
+            Err(err) => {
+                // See `Acl::read` for why this error is expected.
+                if default_acl
+                    && (err.kind() == io::ErrorKind::PermissionDenied
+                        || err.kind() == io::ErrorKind::InvalidInput)
+                    && options.contains(AclOption::IGNORE_EXPECTED_FILE_ERR)
+                    && is_non_directory_fd(fd)
+                {
+                    // Return an empty acl.
+                    Ok(Acl::new(xacl_init(1)?, default_acl))
+                } else {
+                    Err(fd_err(fd.as_raw_fd(), &err))
+                }
+            }
+        }
+    }
+
+    /// Write ACL for an open file descriptor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn write_fd(&self, fd: BorrowedFd, options: AclOption) -> io::Result<()> {
+        let default_acl = options.contains(AclOption::DEFAULT_ACL);
+
+        if default_acl && is_non_directory_fd(fd) {
+            if self.is_empty() && options.contains(AclOption::IGNORE_EXPECTED_FILE_ERR) {
+                return Ok(());
+            }
+
+            let err = Error::NotSupported("Non-directory does not have default ACL".to_string());
+            return Err(fd_err(fd.as_raw_fd(), &err.into()));
+        }
+
+        if let Err(err) = xacl_set_fd(fd.as_raw_fd(), self.acl, default_acl) {
+            return Err(fd_err(fd.as_raw_fd(), &err));
+        }
+
+        Ok(())
+    }
+
     /// Compute mask.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    fn compute_mask_perms(entries: &[AclEntry], filter: (Flag, Flag)) -> Option<Perm> {
+    pub(crate) fn compute_mask_perms(entries: &[AclEntry], filter: (Flag, Flag)) -> Option<Perm> {
         let mut perms = Perm::empty();
         let mut need_mask = false;
 
@@ -158,7 +221,10 @@ impl Acl {
     ///
     /// It is valid for there to be zero entries.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    fn find_missing_entries(entries: &[AclEntry], filter: (Flag, Flag)) -> Option<AclEntryKind> {
+    pub(crate) fn find_missing_entries(
+        entries: &[AclEntry],
+        filter: (Flag, Flag),
+    ) -> Option<AclEntryKind> {
         let mut miss_user = true;
         let mut miss_group = true;
         let mut miss_other = true;
@@ -197,10 +263,12 @@ impl Acl {
     /// On Linux, if there is no mask `AclEntry`, one will be computed and
     /// added, if needed.
     ///
+    /// User and group names are resolved with `resolver`.
+    ///
     /// # Errors
     ///
     /// Returns an [`io::Error`] on failure.
-    pub fn from_entries(entries: &[AclEntry]) -> io::Result<Acl> {
+    pub fn from_entries(entries: &[AclEntry], resolver: &dyn PrincipalResolver) -> io::Result<Acl> {
         let new_acl = xacl_init(entries.len())?;
 
         // Use the smart pointer form of scopeguard; `acl_p` can change value
@@ -210,23 +278,26 @@ impl Acl {
         });
 
         for (i, entry) in entries.iter().enumerate() {
-            if let Err(err) = entry.add_to_acl(&mut acl_p) {
-                return fail_custom(&format!("entry {i}: {err}"));
+            if let Err(err) = entry.add_to_acl(&mut acl_p, resolver) {
+                return Err(context_err(&format!("entry {i}"), &err));
             }
         }
 
         // Check for missing required entries.
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         if let Some(kind) = Acl::find_missing_entries(entries, (Flag::empty(), Flag::empty())) {
-            return fail_custom(&format!("missing required entry \"{kind}\""));
+            return fail(Error::MissingRequiredEntry {
+                kind,
+                default: false,
+            });
         }
 
         // Check if we need to add a mask entry.
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         if let Some(mask_perms) = Acl::compute_mask_perms(entries, (Flag::empty(), Flag::empty())) {
             let mask = AclEntry::allow_mask(mask_perms, None);
-            if let Err(err) = mask.add_to_acl(&mut acl_p) {
-                return fail_custom(&format!("entry -1: {err}"));
+            if let Err(err) = mask.add_to_acl(&mut acl_p, resolver) {
+                return Err(context_err("entry -1", &err));
             }
         }
 
@@ -240,12 +311,17 @@ impl Acl {
     /// If there is no mask `AclEntry` in an ACL, one will be computed and
     /// added, if needed.
     ///
+    /// User and group names are resolved with `resolver`.
+    ///
     /// # Errors
     ///
     /// Returns an [`io::Error`] on failure.
     #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
     #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
-    pub fn from_unified_entries(entries: &[AclEntry]) -> io::Result<(Acl, Acl)> {
+    pub fn from_unified_entries(
+        entries: &[AclEntry],
+        resolver: &dyn PrincipalResolver,
+    ) -> io::Result<(Acl, Acl)> {
         let new_access = xacl_init(entries.len())?;
         let new_default = xacl_init(entries.len())?;
 
@@ -261,23 +337,29 @@ impl Acl {
 
         for (i, entry) in entries.iter().enumerate() {
             let result = if entry.flags.contains(Flag::DEFAULT) {
-                entry.add_to_acl(&mut default_p)
+                entry.add_to_acl(&mut default_p, resolver)
             } else {
-                entry.add_to_acl(&mut access_p)
+                entry.add_to_acl(&mut access_p, resolver)
             };
             if let Err(err) = result {
-                return fail_custom(&format!("entry {i}: {err}"));
+                return Err(context_err(&format!("entry {i}"), &err));
             }
         }
 
         if xacl_is_posix(*access_p) {
             // Check for missing entries in both access and default entries.
             if let Some(kind) = Acl::find_missing_entries(entries, (Flag::empty(), Flag::DEFAULT)) {
-                return fail_custom(&format!("missing required entry \"{kind}\""));
+                return fail(Error::MissingRequiredEntry {
+                    kind,
+                    default: false,
+                });
             }
 
             if let Some(kind) = Acl::find_missing_entries(entries, (Flag::DEFAULT, Flag::DEFAULT)) {
-                return fail_custom(&format!("missing required default entry \"{kind}\""));
+                return fail(Error::MissingRequiredEntry {
+                    kind,
+                    default: true,
+                });
             }
 
             // Check if we need to add a mask entry.
@@ -285,8 +367,8 @@ impl Acl {
                 Acl::compute_mask_perms(entries, (Flag::empty(), Flag::DEFAULT))
             {
                 let mask = AclEntry::allow_mask(mask_perms, None);
-                if let Err(err) = mask.add_to_acl(&mut access_p) {
-                    return fail_custom(&format!("mask entry: {err}"));
+                if let Err(err) = mask.add_to_acl(&mut access_p, resolver) {
+                    return Err(context_err("mask entry", &err));
                 }
             }
 
@@ -294,8 +376,8 @@ impl Acl {
                 Acl::compute_mask_perms(entries, (Flag::DEFAULT, Flag::DEFAULT))
             {
                 let mask = AclEntry::allow_mask(mask_perms, Flag::DEFAULT);
-                if let Err(err) = mask.add_to_acl(&mut default_p) {
-                    return fail_custom(&format!("default mask entry: {err}"));
+                if let Err(err) = mask.add_to_acl(&mut default_p, resolver) {
+                    return Err(context_err("default mask entry", &err));
                 }
             }
         }
@@ -308,14 +390,17 @@ impl Acl {
 
     /// Return ACL as a vector of [`AclEntry`].
     ///
+    /// Users and groups are named by looking up their uid/gid with
+    /// `resolver`.
+    ///
     /// # Errors
     ///
     /// Returns an [`io::Error`] on failure.
-    pub fn entries(&self) -> io::Result<Vec<AclEntry>> {
+    pub fn entries(&self, resolver: &dyn PrincipalResolver) -> io::Result<Vec<AclEntry>> {
         let mut entries = Vec::<AclEntry>::with_capacity(8);
 
         xacl_foreach(self.acl, |entry_p| {
-            let entry = AclEntry::from_raw(entry_p, self.acl)?;
+            let entry = AclEntry::from_raw(entry_p, self.acl, resolver)?;
             entries.push(entry);
             Ok(())
         })?;
@@ -342,7 +427,7 @@ impl Acl {
     pub fn to_string(&self) -> io::Result<String> {
         use std::io::Write;
         let mut buf = Vec::new();
-        for entry in self.entries()? {
+        for entry in self.entries(&crate::resolver::NssResolver)? {
             writeln!(buf, "{entry}")?;
         }
         String::from_utf8(buf).map_err(|err| io::Error::new(io::ErrorKind::Other, err))
@@ -393,6 +478,15 @@ fn is_non_directory(path: &Path, symlink: bool) -> bool {
     result.map_or(false, |meta| !meta.is_dir())
 }
 
+/// Return true if file descriptor refers to an open non-directory.
+fn is_non_directory_fd(fd: BorrowedFd) -> bool {
+    let result = fd
+        .try_clone_to_owned()
+        .and_then(|owned| File::from(owned).metadata());
+
+    result.is_ok_and(|meta| !meta.is_dir())
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -400,13 +494,14 @@ mod acl_tests {
     use super::*;
     use crate::flag::Flag;
     use crate::perm::Perm;
+    use crate::resolver::NssResolver;
     use log::debug;
 
     #[test]
     fn test_read_acl() -> io::Result<()> {
         let file = tempfile::NamedTempFile::new()?;
         let acl = Acl::read(file.as_ref(), AclOption::empty())?;
-        let entries = acl.entries()?;
+        let entries = acl.entries(&NssResolver)?;
 
         #[cfg(target_os = "macos")]
         assert_eq!(entries.len(), 0);
@@ -438,7 +533,7 @@ mod acl_tests {
         ));
 
         let file = tempfile::NamedTempFile::new()?;
-        let acl = Acl::from_entries(&entries)?;
+        let acl = Acl::from_entries(&entries, &NssResolver)?;
         assert!(!acl.is_empty());
         acl.write(file.as_ref(), AclOption::empty())?;
 
@@ -455,7 +550,7 @@ deny:file_inherit,directory_inherit:group:11504:read,write,execute
         );
 
         let acl2 = Acl::read(file.as_ref(), AclOption::empty())?;
-        let entries2 = acl2.entries()?;
+        let entries2 = acl2.entries(&NssResolver)?;
 
         assert_eq!(entries2, entries);
 
@@ -485,7 +580,7 @@ deny:file_inherit,directory_inherit:group:11504:read,write,execute
         entries.push(AclEntry::allow_other(rwx, None));
         // We do not add a mask entry. One will be automatically added.
 
-        let acl = Acl::from_entries(&entries)?;
+        let acl = Acl::from_entries(&entries, &NssResolver)?;
         acl.write(file.as_ref(), AclOption::empty())?;
 
         assert_eq!(
@@ -502,7 +597,7 @@ allow::other::read,write,execute
         );
 
         let acl2 = Acl::read(file.as_ref(), AclOption::empty())?;
-        let mut entries2 = acl2.entries()?;
+        let mut entries2 = acl2.entries(&NssResolver)?;
 
         // Before doing the comparison, add the mask entry.
         entries.push(AclEntry::allow_mask(rwx, None));
@@ -525,11 +620,11 @@ allow::other::read,write,execute
         }
 
         let file = tempfile::NamedTempFile::new()?;
-        let acl = Acl::from_entries(&entries)?;
+        let acl = Acl::from_entries(&entries, &NssResolver)?;
         acl.write(file.as_ref(), AclOption::empty())?;
 
         let acl2 = Acl::read(file.as_ref(), AclOption::empty())?;
-        let entries2 = acl2.entries()?;
+        let entries2 = acl2.entries(&NssResolver)?;
 
         assert_eq!(entries2, entries);
 
@@ -546,7 +641,7 @@ allow::other::read,write,execute
             entries.push(AclEntry::allow_user("11501", rwx, None));
         }
 
-        let err = Acl::from_entries(&entries).err().unwrap();
+        let err = Acl::from_entries(&entries, &NssResolver).err().unwrap();
         assert_eq!(err.to_string(), "Too many ACL entries");
     }
 
@@ -582,38 +677,84 @@ allow::other::read,write,execute
         entries.push(AclEntry::allow_mask(rwx, None));
 
         let path = dir.as_ref();
-        let acl = Acl::from_entries(&entries)?;
+        let acl = Acl::from_entries(&entries, &NssResolver)?;
         acl.write(path, AclOption::DEFAULT_ACL)?;
 
         let acl2 = Acl::read(path, AclOption::empty())?;
         assert_ne!(acl.to_string()?, acl2.to_string()?);
 
         let default_acl = Acl::read(path, AclOption::DEFAULT_ACL)?;
-        let default_entries = default_acl.entries()?;
+        let default_entries = default_acl.entries(&NssResolver)?;
         for entry in &default_entries {
             assert_eq!(entry.flags, Flag::DEFAULT);
         }
 
         // Test deleting a default ACL by passing an empty acl.
         debug!("Test deleting a default ACL");
-        let empty_acl = Acl::from_entries(&[])?;
+        let empty_acl = Acl::from_entries(&[], &NssResolver)?;
         empty_acl.write(path, AclOption::DEFAULT_ACL)?;
         assert!(Acl::read(path, AclOption::DEFAULT_ACL)?.is_empty());
 
         Ok(())
     }
 
+    #[test]
+    fn test_read_acl_fd() -> io::Result<()> {
+        use std::os::unix::io::AsFd;
+
+        let file = tempfile::NamedTempFile::new()?;
+        let acl = Acl::read_fd(file.as_file().as_fd(), AclOption::empty())?;
+        let acl2 = Acl::read(file.as_ref(), AclOption::empty())?;
+
+        assert_eq!(acl.entries(&NssResolver)?, acl2.entries(&NssResolver)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_write_default_acl_fd() -> io::Result<()> {
+        use std::os::unix::io::AsFd;
+
+        let dir = tempfile::tempdir()?;
+        let dir_file = File::open(dir.path())?;
+        let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+
+        let entries = vec![
+            AclEntry::allow_user("", rwx, None),
+            AclEntry::allow_group("", rwx, None),
+            AclEntry::allow_other(Perm::READ, None),
+        ];
+
+        let acl = Acl::from_entries(&entries, &NssResolver)?;
+        acl.write_fd(dir_file.as_fd(), AclOption::DEFAULT_ACL)?;
+
+        let default_acl = Acl::read(dir.path(), AclOption::DEFAULT_ACL)?;
+        assert_eq!(default_acl.to_string()?, "allow:default:user::read,write,execute\nallow:default:group::read,write,execute\nallow:default:other::read\n");
+
+        // Default ACL on a non-directory is an error.
+        let file = tempfile::NamedTempFile::new()?;
+        let err = acl
+            .write_fd(file.as_file().as_fd(), AclOption::DEFAULT_ACL)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Non-directory does not have default ACL"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_from_entries() {
         // 0 entries should result in empty acl.
-        let acl = Acl::from_entries(&[]).unwrap();
+        let acl = Acl::from_entries(&[], &NssResolver).unwrap();
         assert!(acl.is_empty());
 
         // Test named user on MacOS.
         #[cfg(target_os = "macos")]
         {
             let entries = vec![AclEntry::allow_user("500", Perm::EXECUTE, None)];
-            let acl = Acl::from_entries(&entries).unwrap();
+            let acl = Acl::from_entries(&entries, &NssResolver).unwrap();
             assert_eq!(acl.to_string().unwrap(), "allow::user:500:execute\n");
         }
 
@@ -626,11 +767,11 @@ allow::other::read,write,execute
                 AclEntry::allow_user("500", Perm::EXECUTE, None),
             ];
 
-            let err = Acl::from_entries(&entries).err().unwrap();
+            let err = Acl::from_entries(&entries, &NssResolver).err().unwrap();
             assert_eq!(err.to_string(), "missing required entry \"user\"");
 
             entries.push(AclEntry::allow_user("", Perm::READ, None));
-            let acl = Acl::from_entries(&entries).unwrap();
+            let acl = Acl::from_entries(&entries, &NssResolver).unwrap();
 
             #[cfg(target_os = "linux")]
             let expected =
@@ -641,7 +782,7 @@ allow::other::read,write,execute
             assert_eq!(acl.to_string().unwrap(), expected);
 
             entries.push(AclEntry::allow_group("", Perm::WRITE, None));
-            let err = Acl::from_entries(&entries).err().unwrap();
+            let err = Acl::from_entries(&entries, &NssResolver).err().unwrap();
             assert_eq!(err.to_string(), "entry 4: duplicate entry for \"group\"");
         }
     }
@@ -650,7 +791,7 @@ allow::other::read,write,execute
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     fn test_from_unified_entries() {
         // 0 entries should result in empty acls.
-        let (a, d) = Acl::from_unified_entries(&[]).unwrap();
+        let (a, d) = Acl::from_unified_entries(&[], &NssResolver).unwrap();
         assert!(a.is_empty());
         assert!(d.is_empty());
 
@@ -660,7 +801,9 @@ allow::other::read,write,execute
         ];
 
         // Missing required entries.
-        let err = Acl::from_unified_entries(&entries).err().unwrap();
+        let err = Acl::from_unified_entries(&entries, &NssResolver)
+            .err()
+            .unwrap();
         assert_eq!(err.to_string(), "missing required entry \"user\"");
 
         entries.push(AclEntry::allow_group("", Perm::WRITE, None));
@@ -668,14 +811,16 @@ allow::other::read,write,execute
         entries.push(AclEntry::allow_other(Perm::empty(), None));
 
         // Missing required default entries.
-        let err = Acl::from_unified_entries(&entries).err().unwrap();
+        let err = Acl::from_unified_entries(&entries, &NssResolver)
+            .err()
+            .unwrap();
         assert_eq!(err.to_string(), "missing required default entry \"user\"");
 
         entries.push(AclEntry::allow_group("", Perm::WRITE, Flag::DEFAULT));
         entries.push(AclEntry::allow_user("", Perm::READ, Flag::DEFAULT));
         entries.push(AclEntry::allow_other(Perm::empty(), Flag::DEFAULT));
 
-        let (a, d) = Acl::from_unified_entries(&entries).unwrap();
+        let (a, d) = Acl::from_unified_entries(&entries, &NssResolver).unwrap();
 
         #[cfg(target_os = "linux")]
         let expected1 = "allow::user::read\nallow::user:500:execute\nallow::group::write\nallow::mask::write,execute\nallow::other::\n";
@@ -691,7 +836,9 @@ allow::other::read,write,execute
 
         entries.push(AclEntry::allow_group("", Perm::WRITE, Flag::DEFAULT));
 
-        let err = Acl::from_unified_entries(&entries).err().unwrap();
+        let err = Acl::from_unified_entries(&entries, &NssResolver)
+            .err()
+            .unwrap();
         assert_eq!(
             err.to_string(),
             "entry 8: duplicate default entry for \"group\""
@@ -700,7 +847,7 @@ allow::other::read,write,execute
 
     #[test]
     fn test_empty_acl() -> io::Result<()> {
-        let acl = Acl::from_entries(&[])?;
+        let acl = Acl::from_entries(&[], &NssResolver)?;
         assert!(acl.is_empty());
         Ok(())
     }
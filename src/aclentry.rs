@@ -1,10 +1,10 @@
 //! Provides `AclEntry` implementation.
 
-use crate::failx::fail_custom;
 use crate::flag::Flag;
 use crate::format;
 use crate::perm::Perm;
 use crate::qualifier::Qualifier;
+use crate::resolver::PrincipalResolver;
 use crate::util::*;
 
 #[cfg(feature = "serde")]
@@ -50,7 +50,10 @@ pub enum AclEntryKind {
 /// ACL entries are ordered so sorting will automatically put the ACL in
 /// canonical order.
 ///
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Two entries are equal if they have the same kind, name, permissions,
+/// flags and allow value. The `id` field is not compared, since it is only
+/// used when it matches the name.
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct AclEntry {
@@ -61,6 +64,20 @@ pub struct AclEntry {
     /// or decimal uid/gid. On macOS you can use a UUID.
     pub name: String,
 
+    /// Numeric uid/gid of a named user or group, if known.
+    ///
+    /// Entries read from a file keep the uid/gid that `name` was resolved
+    /// from, and entries constructed with [`AclEntry::allow_uid`] or
+    /// [`AclEntry::allow_gid`] have the given id. The id is used instead of
+    /// looking up `name` if it matches `name`: if `name` is the decimal id,
+    /// or the name of the user or group with that id. If `name` is changed
+    /// to another user or group, the id is ignored and `name` is looked up.
+    ///
+    /// The id is not serialized, so the serialized form stays readable by
+    /// older versions of this crate.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub id: Option<u32>,
+
     /// Permission bits for the entry.
     pub perms: Perm,
 
@@ -80,6 +97,18 @@ const fn default_allow() -> bool {
     true
 }
 
+impl PartialEq for AclEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.name == other.name
+            && self.perms == other.perms
+            && self.flags == other.flags
+            && self.allow == other.allow
+    }
+}
+
+impl Eq for AclEntry {}
+
 impl Ord for AclEntry {
     fn cmp(&self, other: &Self) -> Ordering {
         // Entries with flags last.
@@ -125,12 +154,28 @@ impl AclEntry {
         AclEntry {
             kind,
             name: String::from(name),
+            id: None,
             perms,
             flags: flags.unwrap_or_default(),
             allow,
         }
     }
 
+    /// Construct a new access control entry for a uid/gid.
+    #[must_use]
+    fn with_id(
+        kind: AclEntryKind,
+        id: u32,
+        perms: Perm,
+        flags: Option<Flag>,
+        allow: bool,
+    ) -> AclEntry {
+        AclEntry {
+            id: Some(id),
+            ..AclEntry::new(kind, &id.to_string(), perms, flags, allow)
+        }
+    }
+
     /// Construct an ALLOW access control entry for a user.
     #[must_use]
     pub fn allow_user<F>(name: &str, perms: Perm, flags: F) -> AclEntry
@@ -149,6 +194,30 @@ impl AclEntry {
         AclEntry::new(AclEntryKind::Group, name, perms, flags.into(), true)
     }
 
+    /// Construct an ALLOW access control entry for a user by uid.
+    ///
+    /// The entry is named by the decimal uid. The uid is used as is, even if
+    /// there is a user whose name is the same decimal number.
+    #[must_use]
+    pub fn allow_uid<F>(uid: u32, perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        AclEntry::with_id(AclEntryKind::User, uid, perms, flags.into(), true)
+    }
+
+    /// Construct an ALLOW access control entry for a group by gid.
+    ///
+    /// The entry is named by the decimal gid. The gid is used as is, even if
+    /// there is a group whose name is the same decimal number.
+    #[must_use]
+    pub fn allow_gid<F>(gid: u32, perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        AclEntry::with_id(AclEntryKind::Group, gid, perms, flags.into(), true)
+    }
+
     /// Construct an ALLOW access control entry for mask.
     #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
     #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
@@ -193,67 +262,131 @@ impl AclEntry {
         AclEntry::new(AclEntryKind::Group, name, perms, flags.into(), false)
     }
 
+    /// Construct a DENY access control entry for a user by uid.
+    #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
+    #[must_use]
+    pub fn deny_uid<F>(uid: u32, perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        AclEntry::with_id(AclEntryKind::User, uid, perms, flags.into(), false)
+    }
+
+    /// Construct a DENY access control entry for a group by gid.
+    #[cfg(any(docsrs, target_os = "macos", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "macos", target_os = "freebsd"))))]
+    #[must_use]
+    pub fn deny_gid<F>(gid: u32, perms: Perm, flags: F) -> AclEntry
+    where
+        F: Into<Option<Flag>>,
+    {
+        AclEntry::with_id(AclEntryKind::Group, gid, perms, flags.into(), false)
+    }
+
     /// Return an `AclEntry` constructed from a native `acl_entry_t`.
-    pub(crate) fn from_raw(entry: acl_entry_t, acl: acl_t) -> io::Result<AclEntry> {
+    pub(crate) fn from_raw(
+        entry: acl_entry_t,
+        acl: acl_t,
+        resolver: &dyn PrincipalResolver,
+    ) -> io::Result<AclEntry> {
         let (allow, qualifier, perms, flags) = xacl_get_entry(acl, entry)?;
 
-        let (kind, name) = match qualifier {
-            Qualifier::Unknown(s) => (AclEntryKind::Unknown, s),
+        AclEntry::from_qualifier(&qualifier, perms, flags, allow, resolver)
+    }
+
+    /// Return an `AclEntry` constructed from a qualifier.
+    ///
+    /// Users and groups are named by looking up their uid/gid with
+    /// `resolver`. The uid/gid is kept in the entry's `id`.
+    pub(crate) fn from_qualifier(
+        qualifier: &Qualifier,
+        perms: Perm,
+        flags: Flag,
+        allow: bool,
+        resolver: &dyn PrincipalResolver,
+    ) -> io::Result<AclEntry> {
+        let name = qualifier.name(resolver)?;
+        let id = match qualifier {
+            Qualifier::User(id) | Qualifier::Group(id) => Some(*id),
+            _ => None,
+        };
+
+        let kind = match qualifier {
+            Qualifier::Unknown(_) => AclEntryKind::Unknown,
 
             #[cfg(target_os = "macos")]
-            Qualifier::User(_) | Qualifier::Guid(_) => (AclEntryKind::User, qualifier.name()?),
+            Qualifier::User(_) | Qualifier::Guid(_) => AclEntryKind::User,
 
             #[cfg(target_os = "macos")]
-            Qualifier::Group(_) => (AclEntryKind::Group, qualifier.name()?),
+            Qualifier::Group(_) => AclEntryKind::Group,
 
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::User(_) | Qualifier::UserObj => (AclEntryKind::User, qualifier.name()?),
+            Qualifier::User(_) | Qualifier::UserObj => AclEntryKind::User,
 
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Group(_) | Qualifier::GroupObj => (AclEntryKind::Group, qualifier.name()?),
+            Qualifier::Group(_) | Qualifier::GroupObj => AclEntryKind::Group,
 
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Mask => (AclEntryKind::Mask, qualifier.name()?),
+            Qualifier::Mask => AclEntryKind::Mask,
 
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            Qualifier::Other => (AclEntryKind::Other, qualifier.name()?),
+            Qualifier::Other => AclEntryKind::Other,
 
             #[cfg(target_os = "freebsd")]
-            Qualifier::Everyone => (AclEntryKind::Everyone, qualifier.name()?),
+            Qualifier::Everyone => AclEntryKind::Everyone,
         };
 
         Ok(AclEntry {
             kind,
             name,
+            id,
             perms,
             flags,
             allow,
         })
     }
 
-    pub(crate) fn add_to_acl(&self, acl: &mut acl_t) -> io::Result<()> {
-        let qualifier = self.qualifier()?;
+    pub(crate) fn add_to_acl(
+        &self,
+        acl: &mut acl_t,
+        resolver: &dyn PrincipalResolver,
+    ) -> io::Result<()> {
+        let qualifier = self.qualifier(resolver)?;
         xacl_add_entry(acl, self.allow, &qualifier, self.perms, self.flags)?;
 
         Ok(())
     }
 
-    fn qualifier(&self) -> io::Result<Qualifier> {
-        let qualifier = match self.kind {
-            AclEntryKind::User => Qualifier::user_named(&self.name)?,
-            AclEntryKind::Group => Qualifier::group_named(&self.name)?,
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            AclEntryKind::Mask => Qualifier::mask_named(&self.name)?,
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            AclEntryKind::Other => Qualifier::other_named(&self.name)?,
-            #[cfg(target_os = "freebsd")]
-            AclEntryKind::Everyone => Qualifier::everyone_named(&self.name)?,
-            AclEntryKind::Unknown => {
-                return fail_custom("unsupported kind: \"unknown\"");
-            }
+    /// Return the qualifier for this entry.
+    ///
+    /// If the entry has an `id` that matches its name, the id is used as is.
+    /// Otherwise, user and group names are resolved with `resolver`.
+    pub(crate) fn qualifier(&self, resolver: &dyn PrincipalResolver) -> io::Result<Qualifier> {
+        match (self.kind, self.matching_id(resolver)?) {
+            (AclEntryKind::User, Some(uid)) => Ok(Qualifier::User(uid)),
+            (AclEntryKind::Group, Some(gid)) => Ok(Qualifier::Group(gid)),
+            _ => Qualifier::from_kind(self.kind, &self.name, resolver),
+        }
+    }
+
+    /// Return the `id` of a named user or group entry, if it matches the
+    /// name. Only the id is looked up, so a numeric-looking name is not
+    /// mistaken for an id.
+    fn matching_id(&self, resolver: &dyn PrincipalResolver) -> io::Result<Option<u32>> {
+        let Some(id) = self.id else {
+            return Ok(None);
         };
+        if self.name == id.to_string() {
+            return Ok(Some(id));
+        }
 
-        Ok(qualifier)
+        let name = match self.kind {
+            AclEntryKind::User => resolver.uid_to_name(id)?,
+            AclEntryKind::Group => resolver.gid_to_name(id)?,
+            _ => return Ok(None),
+        };
+        Ok((name == self.name).then_some(id))
     }
 }
 
@@ -282,16 +415,38 @@ impl std::str::FromStr for AclEntryKind {
 impl fmt::Display for AclEntry {
     /// Format an `AclEntry` 5-tuple:
     ///   <allow>:<flags>:<kind>:<name>:<perms>
+    ///
+    /// Special characters in the name are written as octal escapes.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let allow = if self.allow { "allow" } else { "deny" };
         write!(
             f,
             "{}:{}:{}:{}:{}",
-            allow, self.flags, self.kind, self.name, self.perms
+            allow,
+            self.flags,
+            self.kind,
+            format::quote_name(&self.name),
+            self.perms
         )
     }
 }
 
+fn parse_name(value: &str) -> Result<String, format::Error> {
+    format::unquote_name(value).ok_or_else(|| {
+        format::Error::Message(format!("Invalid escape sequence in name: `{value}`"))
+    })
+}
+
+/// Parse a field from `format::split_fields`. Errors are reported at the
+/// field's position.
+fn parse_field<T, F>(field: (usize, &str), parse: F) -> Result<T, format::Error>
+where
+    F: FnOnce(&str) -> Result<T, format::Error>,
+{
+    let (offset, value) = field;
+    parse(value).map_err(|err| err.at(offset))
+}
+
 fn parse_allow(value: &str) -> Result<bool, format::Error> {
     let result = match value {
         "allow" => true,
@@ -309,34 +464,34 @@ impl std::str::FromStr for AclEntry {
     type Err = format::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let fields = s.splitn(5, ':').map(str::trim).collect::<Vec<&str>>();
+        let fields = format::split_fields(s, ':', 5);
 
-        let entry = match fields.len() {
-            5 => {
+        let entry = match fields[..] {
+            [allow, flags, kind, name, perms] => {
                 // <allow>:<flags>:<kind>:<name>:<perms>
-                let allow = parse_allow(fields[0])?;
-                let flags = fields[1].parse::<Flag>()?;
-                let kind = fields[2].parse::<AclEntryKind>()?;
-                let name = fields[3];
-                let perms = fields[4].parse::<Perm>()?;
+                let allow = parse_field(allow, parse_allow)?;
+                let flags = parse_field(flags, str::parse::<Flag>)?;
+                let kind = parse_field(kind, str::parse::<AclEntryKind>)?;
+                let name = &parse_field(name, parse_name)?;
+                let perms = parse_field(perms, str::parse::<Perm>)?;
                 AclEntry::new(kind, name, perms, Some(flags), allow)
             }
-            4 => {
+            [flags, kind, name, perms] => {
                 // <flags>:<kind>:<name>:<perms>
                 let allow = true;
-                let flags = fields[0].parse::<Flag>()?;
-                let kind = fields[1].parse::<AclEntryKind>()?;
-                let name = fields[2];
-                let perms = fields[3].parse::<Perm>()?;
+                let flags = parse_field(flags, str::parse::<Flag>)?;
+                let kind = parse_field(kind, str::parse::<AclEntryKind>)?;
+                let name = &parse_field(name, parse_name)?;
+                let perms = parse_field(perms, str::parse::<Perm>)?;
                 AclEntry::new(kind, name, perms, Some(flags), allow)
             }
-            3 => {
+            [kind, name, perms] => {
                 // <kind>:<name>:<perms>
                 let allow = true;
                 let flags = Flag::empty();
-                let kind = fields[0].parse::<AclEntryKind>()?;
-                let name = fields[1];
-                let perms = fields[2].parse::<Perm>()?;
+                let kind = parse_field(kind, str::parse::<AclEntryKind>)?;
+                let name = &parse_field(name, parse_name)?;
+                let perms = parse_field(perms, str::parse::<Perm>)?;
                 AclEntry::new(kind, name, perms, Some(flags), allow)
             }
             _ => return Err(format::Error::Message(format!("Unknown ACL format: `{s}`"))),
@@ -417,6 +572,31 @@ mod aclentry_tests {
         assert_eq!(acl, acl_sorted);
     }
 
+    #[test]
+    fn test_allow_uid() {
+        let entry = AclEntry::allow_uid(1000, Perm::READ, None);
+        assert_eq!(entry.name, "1000");
+        assert_eq!(entry.id, Some(1000));
+        assert_eq!(entry.to_string(), "allow::user:1000:read");
+
+        // The id is used if it matches the name.
+        let resolver = crate::resolver::NssResolver;
+        let mut entry = AclEntry::allow_gid(4242, Perm::READ, None);
+        assert_eq!(entry.qualifier(&resolver).unwrap(), Qualifier::Group(4242));
+
+        // After the name is changed, the id is ignored.
+        entry.name = "0".to_string();
+        assert_eq!(entry.qualifier(&resolver).unwrap(), Qualifier::Group(0));
+        entry.name = "no_such_group".to_string();
+        assert!(entry.qualifier(&resolver).is_err());
+
+        // The id is not compared.
+        assert_eq!(
+            AclEntry::allow_uid(1000, Perm::READ, None),
+            AclEntry::allow_user("1000", Perm::READ, None)
+        );
+    }
+
     #[test]
     fn test_display_kind() {
         assert_eq!(format!("{}", AclEntryKind::User), "user");
@@ -450,9 +630,15 @@ mod aclentry_tests {
     fn test_display_entry_name() {
         let perms = Perm::READ;
 
-        // FIXME: Need to have colons in user names escaped on output!
         let entry = AclEntry::allow_user("x:y", perms, None);
-        assert_eq!(format!("{entry}"), "allow::user:x:y:read");
+        assert_eq!(format!("{entry}"), "allow::user:x\\072y:read");
+
+        let entry = AclEntry::allow_user("DOMAIN\\john smith#1", perms, None);
+        assert_eq!(
+            format!("{entry}"),
+            "allow::user:DOMAIN\\134john\\040smith\\0431:read"
+        );
+        assert_eq!(entry.to_string().parse::<AclEntry>().unwrap(), entry);
     }
 
     #[test]
@@ -509,6 +695,19 @@ mod aclentry_tests {
         // Invalid format.
         let err = "allow:foo".parse::<AclEntry>().unwrap_err();
         assert_eq!(err.to_string(), "Unknown ACL format: `allow:foo`");
+
+        // Invalid escape sequence in name.
+        let err = "user:x\\9:read".parse::<AclEntry>().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid escape sequence in name: `x\\9`");
+        assert_eq!(err.offset(), 5);
+
+        // Error is reported at the misspelled permission.
+        let err = "allow:: user:x:read, wrte".parse::<AclEntry>().unwrap_err();
+        assert_eq!(err.offset(), 21);
+        assert_eq!(
+            err.to_string().split(',').next(),
+            Some("unknown variant `wrte`")
+        );
     }
 
     #[test]
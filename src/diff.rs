@@ -0,0 +1,278 @@
+//! Implements `diff` and `apply` for lists of ACL entries.
+
+use crate::aclentry::AclEntry;
+use crate::perm::Perm;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Change to the permissions of an entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct AclEntryChange {
+    /// Entry after the change.
+    pub entry: AclEntry,
+
+    /// Permissions added to the entry.
+    pub added: Perm,
+
+    /// Permissions removed from the entry.
+    pub removed: Perm,
+}
+
+/// Differences between two lists of ACL entries, returned by [`diff`].
+///
+/// Entries are matched by principal: the kind, name, flags and allow value.
+/// Names are compared as is, without looking them up, so `alice` and `1000`
+/// are different principals even if they are the same user.
+///
+/// The `Display` format shows one entry per line, prefixed with `+` for an
+/// added entry, `-` for a removed entry, and `~` for a changed entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct AclDiff {
+    /// Entries in the new list, but not the old.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub added: Vec<AclEntry>,
+
+    /// Entries in the old list, but not the new.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub removed: Vec<AclEntry>,
+
+    /// Entries in both lists, with different permissions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub changed: Vec<AclEntryChange>,
+}
+
+impl AclDiff {
+    /// Return true if there are no differences.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for AclDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.removed {
+            writeln!(f, "-{entry}")?;
+        }
+
+        for change in &self.changed {
+            write!(f, "~{}", change.entry)?;
+            if !change.added.is_empty() {
+                write!(f, " +{}", change.added)?;
+            }
+            if !change.removed.is_empty() {
+                write!(f, " -{}", change.removed)?;
+            }
+            writeln!(f)?;
+        }
+
+        for entry in &self.added {
+            writeln!(f, "+{entry}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Return true if two entries are for the same principal.
+fn same_principal(a: &AclEntry, b: &AclEntry) -> bool {
+    a.kind == b.kind && a.name == b.name && a.flags == b.flags && a.allow == b.allow
+}
+
+/// Return the differences between two lists of ACL entries.
+///
+/// Added and changed entries are listed in the order of `new`, and removed
+/// entries in the order of `old`. Use [`apply`] to make the same changes to
+/// another list of entries.
+///
+/// ```
+/// use exacl::{diff, AclEntry, Perm};
+///
+/// let old = vec![
+///     AclEntry::allow_user("alice", Perm::READ, None),
+///     AclEntry::allow_group("staff", Perm::READ, None),
+/// ];
+/// let new = vec![
+///     AclEntry::allow_user("alice", Perm::READ | Perm::WRITE, None),
+///     AclEntry::allow_user("bob", Perm::READ, None),
+/// ];
+///
+/// let changes = diff(&old, &new);
+/// assert_eq!(
+///     changes.to_string(),
+///     "-allow::group:staff:read\n~allow::user:alice:read,write +write\n+allow::user:bob:read\n"
+/// );
+/// ```
+#[must_use]
+pub fn diff(old: &[AclEntry], new: &[AclEntry]) -> AclDiff {
+    let mut result = AclDiff::default();
+
+    for entry in new {
+        match old.iter().find(|e| same_principal(e, entry)) {
+            Some(prev) if prev.perms != entry.perms => result.changed.push(AclEntryChange {
+                entry: entry.clone(),
+                added: entry.perms - prev.perms,
+                removed: prev.perms - entry.perms,
+            }),
+            Some(_) => (),
+            None => result.added.push(entry.clone()),
+        }
+    }
+
+    result.removed = old
+        .iter()
+        .filter(|entry| !new.iter().any(|e| same_principal(e, entry)))
+        .cloned()
+        .collect();
+
+    result
+}
+
+/// Apply the differences returned by [`diff`] to a list of ACL entries.
+///
+/// Changes are applied as a delta, so they can be replayed onto entries
+/// that differ from the ones the diff was made from:
+///
+/// - A removed entry is removed, whatever its permissions are. If there is
+///   no entry for the principal, nothing is removed.
+/// - A changed entry has the added permissions set and the removed
+///   permissions cleared. Other permissions are kept. If there is no entry
+///   for the principal, the changed entry is added.
+/// - An added entry replaces the entry for the same principal, or is added
+///   at the end.
+///
+/// The order of the remaining entries is kept.
+#[must_use]
+pub fn apply(entries: &[AclEntry], diff: &AclDiff) -> Vec<AclEntry> {
+    let mut result: Vec<AclEntry> = entries
+        .iter()
+        .filter(|entry| !diff.removed.iter().any(|e| same_principal(e, entry)))
+        .cloned()
+        .collect();
+
+    for change in &diff.changed {
+        match result.iter_mut().find(|e| same_principal(e, &change.entry)) {
+            Some(entry) => entry.perms = (entry.perms - change.removed) | change.added,
+            None => result.push(change.entry.clone()),
+        }
+    }
+
+    for added in &diff.added {
+        match result.iter_mut().find(|e| same_principal(e, added)) {
+            Some(entry) => *entry = added.clone(),
+            None => result.push(added.clone()),
+        }
+    }
+
+    result
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod diff_tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            AclEntry::allow_user("a", Perm::READ | Perm::EXECUTE, None),
+            AclEntry::allow_user("b", Perm::READ, None),
+            AclEntry::allow_group("c", Perm::WRITE, None),
+        ];
+        let new = vec![
+            AclEntry::allow_group("d", Perm::READ, None),
+            AclEntry::allow_user("a", Perm::READ | Perm::WRITE, None),
+            AclEntry::allow_group("c", Perm::WRITE, None),
+        ];
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            AclDiff {
+                added: vec![AclEntry::allow_group("d", Perm::READ, None)],
+                removed: vec![AclEntry::allow_user("b", Perm::READ, None)],
+                changed: vec![AclEntryChange {
+                    entry: AclEntry::allow_user("a", Perm::READ | Perm::WRITE, None),
+                    added: Perm::WRITE,
+                    removed: Perm::EXECUTE,
+                }],
+            }
+        );
+        assert_eq!(
+            changes.to_string(),
+            "-allow::user:b:read
+~allow::user:a:read,write +write -execute
++allow::group:d:read
+"
+        );
+
+        assert!(diff(&new, &new).is_empty());
+        assert_eq!(diff(&new, &new).to_string(), "");
+
+        // Applying a diff to the old entries gives the new entries, in a
+        // different order.
+        let mut result = apply(&old, &changes);
+        result.sort();
+        let mut expected = new;
+        expected.sort();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_apply() {
+        let changes = AclDiff {
+            added: vec![AclEntry::allow_user("a", Perm::READ, None)],
+            removed: vec![AclEntry::allow_user("b", Perm::READ, None)],
+            changed: vec![
+                AclEntryChange {
+                    entry: AclEntry::allow_group("c", Perm::READ | Perm::WRITE, None),
+                    added: Perm::WRITE,
+                    removed: Perm::EXECUTE,
+                },
+                AclEntryChange {
+                    entry: AclEntry::allow_group("d", Perm::READ, None),
+                    added: Perm::READ,
+                    removed: Perm::empty(),
+                },
+            ],
+        };
+
+        let entries = vec![
+            AclEntry::allow_user("a", Perm::WRITE, None),
+            AclEntry::allow_user("b", Perm::WRITE, None),
+            AclEntry::allow_group("c", Perm::EXECUTE, None),
+            AclEntry::allow_group("e", Perm::EXECUTE, None),
+        ];
+
+        assert_eq!(
+            apply(&entries, &changes),
+            vec![
+                AclEntry::allow_user("a", Perm::READ, None),
+                AclEntry::allow_group("c", Perm::WRITE, None),
+                AclEntry::allow_group("e", Perm::EXECUTE, None),
+                AclEntry::allow_group("d", Perm::READ, None),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    fn test_diff_default() {
+        use crate::flag::Flag;
+
+        let old = vec![AclEntry::allow_user("a", Perm::READ, None)];
+        let new = vec![AclEntry::allow_user("a", Perm::READ, Flag::DEFAULT)];
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.added, new);
+        assert_eq!(changes.removed, old);
+        assert!(changes.changed.is_empty());
+    }
+}
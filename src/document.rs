@@ -0,0 +1,317 @@
+//! Provides `AclDocument`, an ACL together with the metadata of its file.
+
+use crate::aclentry::AclEntry;
+use crate::error::Error;
+use crate::failx::{fail, path_err};
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::flag::Flag;
+use crate::resolver::{self, NssResolver, PrincipalResolver};
+use crate::{getfacl_with, setfacl_with, AclOption};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::os::unix::fs::{chown, lchown, FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Mask of the permission bits, including setuid, setgid and sticky.
+const MODE_BITS: u32 = 0o7777;
+
+/// Mask of the setuid, setgid and sticky bits.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+const SPECIAL_BITS: u32 = 0o7000;
+
+/// Type of file described by an [`AclDocument`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FileKind {
+    /// Regular file.
+    File,
+
+    /// Directory.
+    Directory,
+
+    /// Symbolic link.
+    Symlink,
+
+    /// Block device.
+    BlockDevice,
+
+    /// Character device.
+    CharDevice,
+
+    /// Named pipe.
+    Fifo,
+
+    /// Unix domain socket.
+    Socket,
+}
+
+impl From<fs::FileType> for FileKind {
+    fn from(file_type: fs::FileType) -> FileKind {
+        if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_block_device() {
+            FileKind::BlockDevice
+        } else if file_type.is_char_device() {
+            FileKind::CharDevice
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else {
+            FileKind::File
+        }
+    }
+}
+
+/// ACL of a file, together with the file's path, owner, group and mode.
+///
+/// The access and default ACL are kept in separate lists. Entries in
+/// `default` don't have the [`Flag::DEFAULT`](crate::Flag::DEFAULT) flag.
+/// The default ACL is only supported on Linux and FreeBSD.
+///
+/// With the `serde` feature, a document can be serialized, for example as
+/// JSON. `version` is the format version of the document, which is
+/// [`AclDocument::VERSION`] for a document read by this version of the
+/// crate.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::AclDocument;
+///
+/// let doc = AclDocument::read("./tmp/foo", None)?;
+/// println!("{} is owned by {}", doc.path.display(), doc.owner);
+/// doc.apply("./tmp/bar", None)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct AclDocument {
+    /// Format version of the document.
+    pub version: u32,
+
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// Name of the file's owner, or decimal uid.
+    pub owner: String,
+
+    /// Name of the file's group, or decimal gid.
+    pub group: String,
+
+    /// Permission bits of the file, including setuid, setgid and sticky.
+    pub mode: u32,
+
+    /// Type of the file.
+    pub file_type: FileKind,
+
+    /// Entries of the access ACL.
+    pub access: Vec<AclEntry>,
+
+    /// Entries of the default ACL.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub default: Vec<AclEntry>,
+}
+
+impl AclDocument {
+    /// Format version of documents returned by [`AclDocument::read`].
+    pub const VERSION: u32 = 1;
+
+    /// Read the ACL and metadata of a file.
+    ///
+    /// If `options` contains [`AclOption::SYMLINK_ACL`], a symbolic link is
+    /// read instead of the file it points to. If `options` contains
+    /// [`AclOption::NUMERIC_IDS`], the owner, group and entries use decimal
+    /// uid/gid. Both the access and default ACL are read; the
+    /// [`AclOption::ACCESS_ACL`] and [`AclOption::DEFAULT_ACL`] options are
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn read<P, O>(path: P, options: O) -> io::Result<AclDocument>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        AclDocument::read_with(path, options, &NssResolver)
+    }
+
+    /// Read the ACL and metadata of a file, using `resolver` to convert
+    /// uid/gid to user/group names.
+    ///
+    /// Works like [`AclDocument::read`], which uses
+    /// [`NssResolver`](crate::NssResolver). With [`AclOption::NUMERIC_IDS`],
+    /// names are not looked up and `resolver` is not used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] on failure.
+    pub fn read_with<P, O, R>(path: P, options: O, resolver: &R) -> io::Result<AclDocument>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+        R: PrincipalResolver + ?Sized,
+    {
+        let path = path.as_ref();
+        let options =
+            options.into().unwrap_or_default() - (AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL);
+        let resolver = resolver::for_options(options, &resolver);
+
+        let meta = metadata(path, options)?;
+        let entries = getfacl_with(path, options, resolver)?;
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let (access, default) = {
+            let (mut default, access): (Vec<_>, Vec<_>) = entries
+                .into_iter()
+                .partition(|entry| entry.flags.contains(Flag::DEFAULT));
+            for entry in &mut default {
+                entry.flags.remove(Flag::DEFAULT);
+            }
+            (access, default)
+        };
+
+        #[cfg(target_os = "macos")]
+        let (access, default) = (entries, Vec::new());
+
+        Ok(AclDocument {
+            version: AclDocument::VERSION,
+            path: path.to_path_buf(),
+            owner: resolver.uid_to_name(meta.uid())?,
+            group: resolver.gid_to_name(meta.gid())?,
+            mode: meta.mode() & MODE_BITS,
+            file_type: FileKind::from(meta.file_type()),
+            access,
+            default,
+        })
+    }
+
+    /// Set the owner, group, mode and ACL of a file from the document.
+    ///
+    /// The document's `path` and `file_type` are not used; `path` may name
+    /// any file. The owner and group are only changed if they differ from
+    /// the file's. On Linux and FreeBSD, the read/write/execute bits of the
+    /// mode come from the access ACL, and only the setuid, setgid and sticky
+    /// bits of `mode` are used.
+    ///
+    /// If `options` contains [`AclOption::SYMLINK_ACL`], a symbolic link is
+    /// changed instead of the file it points to. If `options` contains
+    /// [`AclOption::NUMERIC_IDS`], the owner, group and names in entries must
+    /// be decimal uid/gid; they are not looked up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the document's version is not supported,
+    /// or on failure.
+    pub fn apply<P, O>(&self, path: P, options: O) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+    {
+        self.apply_with(path, options, &NssResolver)
+    }
+
+    /// Set the owner, group, mode and ACL of a file from the document, using
+    /// `resolver` to convert user/group names to uid/gid.
+    ///
+    /// Works like [`AclDocument::apply`], which uses
+    /// [`NssResolver`](crate::NssResolver). With [`AclOption::NUMERIC_IDS`],
+    /// names must be decimal uid/gid and `resolver` is not used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the document's version is not supported,
+    /// or on failure.
+    pub fn apply_with<P, O, R>(&self, path: P, options: O, resolver: &R) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        O: Into<Option<AclOption>>,
+        R: PrincipalResolver + ?Sized,
+    {
+        if self.version > AclDocument::VERSION {
+            return fail(Error::NotSupported(format!(
+                "unsupported document version: {}",
+                self.version
+            )));
+        }
+
+        #[cfg(target_os = "macos")]
+        if !self.default.is_empty() {
+            return fail(Error::NotSupported(
+                "default ACL is not supported on macOS".to_string(),
+            ));
+        }
+
+        let path = path.as_ref();
+        let options =
+            options.into().unwrap_or_default() - (AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL);
+        let resolver = resolver::for_options(options, &resolver);
+
+        // Only change owner and group when they differ, so that an
+        // unprivileged user can apply a document to their own files.
+        let meta = metadata(path, options)?;
+        let uid = Some(resolver.name_to_uid(&self.owner)?).filter(|uid| *uid != meta.uid());
+        let gid = Some(resolver.name_to_gid(&self.group)?).filter(|gid| *gid != meta.gid());
+        if uid.is_some() || gid.is_some() {
+            let result = if options.contains(AclOption::SYMLINK_ACL) {
+                lchown(path, uid, gid)
+            } else {
+                chown(path, uid, gid)
+            };
+            result.map_err(|err| path_err(path, &err))?;
+        }
+
+        setfacl_with(&[path], &self.entries(), options, resolver)?;
+
+        // Changing the owner or ACL may clear setuid/setgid, so set the mode
+        // last. The mode of a symbolic link itself is not changed.
+        let meta = metadata(path, options)?;
+        if meta.file_type().is_symlink() {
+            return Ok(());
+        }
+        let mode = meta.mode() & MODE_BITS;
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let new_mode = (mode & !SPECIAL_BITS) | (self.mode & SPECIAL_BITS);
+        #[cfg(target_os = "macos")]
+        let new_mode = self.mode & MODE_BITS;
+        if new_mode != mode {
+            let perms = fs::Permissions::from_mode(new_mode);
+            fs::set_permissions(path, perms).map_err(|err| path_err(path, &err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the access and default entries in one list, as used by
+    /// [`setfacl`](crate::setfacl).
+    fn entries(&self) -> Vec<AclEntry> {
+        let mut entries = self.access.clone();
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        entries.extend(self.default.iter().map(|entry| {
+            let mut entry = entry.clone();
+            entry.flags |= Flag::DEFAULT;
+            entry
+        }));
+
+        entries
+    }
+}
+
+/// Return the metadata of a file, or of a symbolic link itself if `options`
+/// contains [`AclOption::SYMLINK_ACL`].
+fn metadata(path: &Path, options: AclOption) -> io::Result<fs::Metadata> {
+    let meta = if options.contains(AclOption::SYMLINK_ACL) {
+        fs::symlink_metadata(path)
+    } else {
+        fs::metadata(path)
+    };
+    meta.map_err(|err| path_err(path, &err))
+}
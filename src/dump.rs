@@ -0,0 +1,401 @@
+//! Implements `getfacl -R` style dumps of a directory tree, and restoring
+//! them like `setfacl --restore`.
+
+use crate::aclentry::AclEntry;
+use crate::error::Error;
+use crate::failx::{fail, path_err};
+use crate::format::{quote, unquote};
+use crate::posix_text::{parse_line, to_posix_text, TextOption};
+use crate::recursive::{getfacl_recursive_with, RecursiveOption};
+use crate::resolver::{NssResolver, NumericResolver, PrincipalResolver};
+use crate::{setfacl_with, AclOption};
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+
+/// Mask of the setuid, setgid and sticky bits.
+const SPECIAL_BITS: u32 = 0o7000;
+
+/// Return the value of the `# flags:` header for a mode, or None if no flag
+/// is set.
+fn flags_text(mode: u32) -> Option<String> {
+    if mode & SPECIAL_BITS == 0 {
+        return None;
+    }
+
+    let flag = |bit: u32, ch: char| if mode & bit == 0 { '-' } else { ch };
+    Some(
+        [flag(0o4000, 's'), flag(0o2000, 's'), flag(0o1000, 't')]
+            .iter()
+            .collect(),
+    )
+}
+
+/// Parse the value of a `# flags:` header.
+fn parse_flags(s: &str) -> Option<u32> {
+    let mut chars = s.chars();
+    let mut mode = 0;
+    for (bit, ch) in [(0o4000, 's'), (0o2000, 's'), (0o1000, 't')] {
+        match chars.next()? {
+            '-' => (),
+            c if c == ch => mode |= bit,
+            _ => return None,
+        }
+    }
+    chars.next().is_none().then_some(mode)
+}
+
+/// Return the resolver for the owner and group headers.
+fn resolver_for(numeric_ids: bool, resolver: &dyn PrincipalResolver) -> &dyn PrincipalResolver {
+    if numeric_ids {
+        &NumericResolver
+    } else {
+        resolver
+    }
+}
+
+/// Return the path of `path` relative to `root`, as written in a dump.
+fn relative_name(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+        Ok(rel) => quote(rel.as_os_str().as_bytes()),
+        Err(_) => quote(path.as_os_str().as_bytes()),
+    }
+}
+
+/// Write the ACL's of a directory tree in the format of `getfacl -R`.
+///
+/// For each file and directory under `root`, writes a block with the path,
+/// owner, group and setuid/setgid/sticky flags, followed by the access and
+/// default ACL entries in the text form of [`to_posix_text`]. Blocks are
+/// separated by an empty line.
+///
+/// Paths are written relative to `root`; `root` itself is written as `.`.
+/// Symbolic links in the tree are skipped, unless `options` contains
+/// [`RecursiveOption::FOLLOW_SYMLINKS`].
+///
+/// If `options` contains [`RecursiveOption::NUMERIC_IDS`], the owner, group
+/// and named entries are written as decimal uid/gid, like `getfacl -n`. Use
+/// this for backups that are restored on another host.
+///
+/// # Sample Output
+///
+/// ```text
+/// # file: .
+/// # owner: alice
+/// # group: staff
+/// # flags: -s-
+/// user::rwx
+/// group::r-x
+/// other::r-x
+/// default:user::rwx
+/// default:group::r-x
+/// default:other::r-x
+///
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Stops at the first error.
+pub fn dump_tree<P, W, O>(root: P, writer: W, options: O) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    W: io::Write,
+    O: Into<Option<RecursiveOption>>,
+{
+    dump_tree_with(root, writer, options, &NssResolver)
+}
+
+/// Write the ACL's of a directory tree in the format of `getfacl -R`, using
+/// `resolver` to convert uid/gid to user/group names.
+///
+/// Works like [`dump_tree`], which uses [`NssResolver`](crate::NssResolver).
+/// With [`RecursiveOption::NUMERIC_IDS`], names are not looked up and
+/// `resolver` is not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Stops at the first error.
+pub fn dump_tree_with<P, W, O, R>(
+    root: P,
+    mut writer: W,
+    options: O,
+    resolver: &R,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    W: io::Write,
+    O: Into<Option<RecursiveOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let root = root.as_ref();
+    let options = options.into().unwrap_or_default();
+    let resolver = resolver_for(options.contains(RecursiveOption::NUMERIC_IDS), &resolver);
+
+    for (path, result) in getfacl_recursive_with(root, options, resolver) {
+        let entries = result?;
+        let meta = fs::metadata(&path).map_err(|err| path_err(&path, &err))?;
+
+        writeln!(writer, "# file: {}", relative_name(root, &path))?;
+        writeln!(writer, "# owner: {}", resolver.uid_to_name(meta.uid())?)?;
+        writeln!(writer, "# group: {}", resolver.gid_to_name(meta.gid())?)?;
+        if let Some(flags) = flags_text(meta.mode()) {
+            writeln!(writer, "# flags: {flags}")?;
+        }
+        write!(
+            writer,
+            "{}",
+            to_posix_text(&entries, TextOption::EFFECTIVE)?
+        )?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// One block of a dump: a file and the ACL to restore.
+#[derive(Default)]
+struct Block {
+    path: Option<PathBuf>,
+    owner: Option<String>,
+    group: Option<String>,
+    flags: u32,
+    entries: Vec<AclEntry>,
+}
+
+impl Block {
+    /// Restore owner, group, ACL and flags of the file.
+    ///
+    /// If `numeric_ids` is true, the owner, group and names in entries must
+    /// be decimal uid/gid; they are not looked up. Otherwise, they are looked
+    /// up with `resolver`.
+    fn apply(
+        &self,
+        base_dir: &Path,
+        numeric_ids: bool,
+        resolver: &dyn PrincipalResolver,
+    ) -> io::Result<()> {
+        let Some(name) = &self.path else {
+            return Ok(());
+        };
+        let path = base_dir.join(name);
+        let meta = fs::metadata(&path).map_err(|err| path_err(&path, &err))?;
+
+        // Only change owner and group when they differ, so that a restore
+        // by an unprivileged user succeeds when there is nothing to change.
+        let resolver = resolver_for(numeric_ids, resolver);
+        let uid = self.owner.as_deref().map(|name| resolver.name_to_uid(name));
+        let gid = self.group.as_deref().map(|name| resolver.name_to_gid(name));
+        let uid = uid.transpose()?.filter(|uid| *uid != meta.uid());
+        let gid = gid.transpose()?.filter(|gid| *gid != meta.gid());
+        if uid.is_some() || gid.is_some() {
+            chown(&path, uid, gid).map_err(|err| path_err(&path, &err))?;
+        }
+
+        let acl_options = if numeric_ids {
+            AclOption::NUMERIC_IDS
+        } else {
+            AclOption::empty()
+        };
+        // A block may only have headers, e.g. for a file whose ACL was
+        // removed from the dump by hand.
+        if !self.entries.is_empty() {
+            setfacl_with(&[&path], &self.entries, acl_options, resolver)?;
+        }
+
+        // Changing the owner may clear setuid/setgid, so set flags last.
+        let mode = fs::metadata(&path)
+            .map_err(|err| path_err(&path, &err))?
+            .mode();
+        if mode & SPECIAL_BITS != self.flags {
+            let perms = fs::Permissions::from_mode((mode & 0o777) | self.flags);
+            fs::set_permissions(&path, perms).map_err(|err| path_err(&path, &err))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Return a parse error for the given line and column.
+fn parse_err<T>(line: usize, column: usize, message: &str) -> io::Result<T> {
+    fail(Error::Parse {
+        line,
+        column,
+        message: message.to_string(),
+    })
+}
+
+/// Return true if `path` is relative and has no `..` component, so it can't
+/// name a file outside of the base directory.
+fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Restore ACL's from the output of [`dump_tree`] or `getfacl -R`.
+///
+/// Reads blocks in the format written by [`dump_tree`]. For each block, sets
+/// the owner, group, ACL and setuid/setgid/sticky flags of the file. Paths
+/// are resolved against `base_dir`. The owner and group are only changed
+/// when they differ from the file's current owner and group. A block without
+/// entries only restores the owner, group and flags.
+///
+/// Absolute paths and paths with a `..` component are rejected, so a dump
+/// can't change files outside of `base_dir`. Symbolic links under
+/// `base_dir` are still followed.
+///
+/// If `options` contains [`RecursiveOption::NUMERIC_IDS`], the owner, group
+/// and names in entries must be decimal uid/gid, as written by [`dump_tree`]
+/// with the same option; they are not looked up. Other options are ignored.
+///
+/// This is the equivalent of `setfacl --restore`.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Stops at the first error. Files in
+/// earlier blocks are left changed.
+pub fn restore<R, P, O>(reader: R, base_dir: P, options: O) -> io::Result<()>
+where
+    R: io::Read,
+    P: AsRef<Path>,
+    O: Into<Option<RecursiveOption>>,
+{
+    restore_with(reader, base_dir, options, &NssResolver)
+}
+
+/// Restore ACL's from the output of [`dump_tree`] or `getfacl -R`, using
+/// `resolver` to convert user/group names to uid/gid.
+///
+/// Works like [`restore`], which uses [`NssResolver`](crate::NssResolver).
+/// With [`RecursiveOption::NUMERIC_IDS`], names must be decimal uid/gid and
+/// `resolver` is not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. Stops at the first error. Files in
+/// earlier blocks are left changed.
+pub fn restore_with<T, P, O, R>(reader: T, base_dir: P, options: O, resolver: &R) -> io::Result<()>
+where
+    T: io::Read,
+    P: AsRef<Path>,
+    O: Into<Option<RecursiveOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let resolver: &dyn PrincipalResolver = &resolver;
+    let base_dir = base_dir.as_ref();
+    let numeric_ids = options
+        .into()
+        .unwrap_or_default()
+        .contains(RecursiveOption::NUMERIC_IDS);
+    let mut block = Block::default();
+
+    for (i, line_result) in io::BufReader::new(reader).lines().enumerate() {
+        let line = line_result?;
+        let lineno = i + 1;
+
+        let Some(header) = line.strip_prefix("# ") else {
+            if line.trim().is_empty() {
+                block.apply(base_dir, numeric_ids, resolver)?;
+                block = Block::default();
+            } else {
+                let entries = parse_line(&line, lineno)?;
+                if block.path.is_none() && !entries.is_empty() {
+                    return parse_err(lineno, 1, "missing \"# file:\" header");
+                }
+                block.entries.extend(entries);
+            }
+            continue;
+        };
+
+        let Some((key, value)) = header.split_once(": ") else {
+            continue;
+        };
+        // Column of the value, after "# key: ".
+        let column = line.len() - value.len() + 1;
+        match key {
+            "file" => {
+                block.apply(base_dir, numeric_ids, resolver)?;
+                let Some(name) = unquote(value) else {
+                    return parse_err(lineno, column, "invalid escape sequence");
+                };
+                let path = PathBuf::from(std::ffi::OsString::from_vec(name));
+                if !is_contained(&path) {
+                    return parse_err(lineno, column, "path is outside of the base directory");
+                }
+                block = Block {
+                    path: Some(path),
+                    ..Block::default()
+                };
+            }
+            "owner" => block.owner = Some(value.to_string()),
+            "group" => block.group = Some(value.to_string()),
+            "flags" => {
+                let Some(flags) = parse_flags(value) else {
+                    return parse_err(lineno, column, "invalid flags");
+                };
+                block.flags = flags;
+            }
+            // Ignore other comments.
+            _ => (),
+        }
+    }
+
+    block.apply(base_dir, numeric_ids, resolver)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod dump_tests {
+    use super::*;
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(flags_text(0o644), None);
+        assert_eq!(flags_text(0o4755).as_deref(), Some("s--"));
+        assert_eq!(flags_text(0o3777).as_deref(), Some("-st"));
+
+        assert_eq!(parse_flags("s--"), Some(0o4000));
+        assert_eq!(parse_flags("-st"), Some(0o3000));
+        assert_eq!(parse_flags("sst"), Some(0o7000));
+        assert_eq!(parse_flags("t--"), None);
+        assert_eq!(parse_flags("---x"), None);
+    }
+
+    #[test]
+    fn test_restore_errors() {
+        let err = restore(&b"user::rw-\n"[..], ".", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 1: missing \"# file:\" header"
+        );
+
+        let err = restore(&b"# file: a\n# flags: xyz\n"[..], ".", None).unwrap_err();
+        assert_eq!(err.to_string(), "line 2, column 10: invalid flags");
+
+        let err = restore(&b"# file: a\\q\n"[..], ".", None).unwrap_err();
+        assert_eq!(err.to_string(), "line 1, column 9: invalid escape sequence");
+
+        for name in ["/etc/passwd", "../a", "a/../../b"] {
+            let dump = format!("# file: {name}\nuser::rw-\n");
+            let err = restore(dump.as_bytes(), ".", None).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 1, column 9: path is outside of the base directory"
+            );
+        }
+    }
+
+    #[test]
+    fn test_restore_no_entries() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("a"), "")?;
+
+        // A block with only headers doesn't change the ACL.
+        restore(&b"# file: a\n\n# file: a\n"[..], dir.path(), None)?;
+        Ok(())
+    }
+}
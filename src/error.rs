@@ -0,0 +1,301 @@
+//! Implements the typed error returned inside an [`io::Error`], and the
+//! report of parse errors.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Error returned by this crate.
+///
+/// Functions in this crate return an [`io::Error`]. When the error comes from
+/// this crate, the `io::Error` wraps an `Error` that you can inspect with
+/// [`Error::from_io`], instead of matching on the error message.
+///
+/// # Example
+///
+/// ```no_run
+/// use exacl::{getfacl, Error};
+///
+/// if let Err(err) = getfacl("./tmp/foo", None) {
+///     match Error::from_io(&err) {
+///         Some(Error::Os { errno, func, .. }) => eprintln!("{func} failed: {errno}"),
+///         _ => eprintln!("{err}"),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A user name can't be resolved to a uid.
+    UnknownUser(String),
+
+    /// A group name can't be resolved to a gid.
+    UnknownGroup(String),
+
+    /// Two entries have the same principal.
+    DuplicateEntry {
+        /// Principal of the duplicate entry, e.g. "user:500".
+        entry: String,
+        /// True if the entries are in the default ACL.
+        default: bool,
+    },
+
+    /// A required entry (owner, owning group or other) is missing.
+    MissingRequiredEntry {
+        /// Kind of the missing entry.
+        kind: AclEntryKind,
+        /// True if the entry is missing from the default ACL.
+        default: bool,
+    },
+
+    /// An entry is malformed, e.g. it has an unknown tag or an invalid name.
+    InvalidEntry(String),
+
+    /// An option or argument is not valid.
+    InvalidInput(String),
+
+    /// The operation is not supported by the platform or file system.
+    NotSupported(String),
+
+    /// A system call failed.
+    Os {
+        /// File the call was made for, if known.
+        path: Option<PathBuf>,
+        /// Value of errno.
+        errno: i32,
+        /// Name of the function that failed.
+        func: String,
+    },
+
+    /// Text can't be parsed.
+    Parse {
+        /// Line number, starting at 1.
+        line: usize,
+        /// Column number, starting at 1.
+        column: usize,
+        /// Description of the problem.
+        message: String,
+    },
+}
+
+impl Error {
+    /// Return the `Error` inside an [`io::Error`], if there is one.
+    #[must_use]
+    pub fn from_io(err: &io::Error) -> Option<&Error> {
+        let inner = err.get_ref()?;
+        inner
+            .downcast_ref::<Error>()
+            .or_else(|| inner.downcast_ref::<ContextError>().map(|c| &c.error))
+    }
+
+    /// Return the errno value of an [`io::Error`] from a failed system call.
+    ///
+    /// A failed system call is returned as an [`Error::Os`], so
+    /// [`io::Error::raw_os_error`] returns `None`. Use this function instead.
+    ///
+    /// ```
+    /// use exacl::Error;
+    /// use std::io;
+    ///
+    /// let err = io::Error::from(Error::Os { path: None, errno: 2, func: "open".to_string() });
+    /// assert_eq!(err.raw_os_error(), None);
+    /// assert_eq!(Error::errno(&err), Some(2));
+    /// assert_eq!(Error::errno(&io::Error::from_raw_os_error(1)), Some(1));
+    /// ```
+    #[must_use]
+    pub fn errno(err: &io::Error) -> Option<i32> {
+        err.raw_os_error().or_else(|| match Error::from_io(err) {
+            Some(Error::Os { errno, .. }) => Some(*errno),
+            _ => None,
+        })
+    }
+
+    /// Return the [`io::ErrorKind`] used when converting to an [`io::Error`].
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Os { errno, .. } => io::Error::from_raw_os_error(*errno).kind(),
+            Error::Parse { .. } => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Return "default " for an error about the default ACL.
+const fn default_str(default: bool) -> &'static str {
+    if default {
+        "default "
+    } else {
+        ""
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownUser(name) => write!(f, "unknown user name: {name:?}"),
+            Error::UnknownGroup(name) => write!(f, "unknown group name: {name:?}"),
+            Error::DuplicateEntry { entry, default } => {
+                write!(
+                    f,
+                    "duplicate {}entry for \"{entry}\"",
+                    default_str(*default)
+                )
+            }
+            Error::MissingRequiredEntry { kind, default } => {
+                write!(
+                    f,
+                    "missing required {}entry \"{kind}\"",
+                    default_str(*default)
+                )
+            }
+            Error::InvalidEntry(msg) | Error::InvalidInput(msg) | Error::NotSupported(msg) => {
+                write!(f, "{msg}")
+            }
+            Error::Os { path, errno, .. } => {
+                let err = io::Error::from_raw_os_error(*errno);
+                match path {
+                    Some(path) => write!(f, "File {path:?}: {err}"),
+                    None => write!(f, "{err}"),
+                }
+            }
+            Error::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "line {line}, column {column}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        io::Error::new(err.io_kind(), err)
+    }
+}
+
+/// An [`Error`] with a prefix that describes where it happened, e.g. the
+/// index of an entry or a file descriptor.
+#[derive(Debug)]
+pub struct ContextError {
+    context: String,
+    error: Error,
+}
+
+impl ContextError {
+    /// Return an [`io::Error`] that prefixes `error` with `context`.
+    pub fn wrap(context: &str, err: &io::Error) -> io::Error {
+        let Some(inner) = err.get_ref() else {
+            return io::Error::new(err.kind(), format!("{context}: {err}"));
+        };
+
+        let wrapped = if let Some(error) = inner.downcast_ref::<Error>() {
+            ContextError {
+                context: context.to_string(),
+                error: error.clone(),
+            }
+        } else if let Some(c) = inner.downcast_ref::<ContextError>() {
+            ContextError {
+                context: format!("{context}: {}", c.context),
+                error: c.error.clone(),
+            }
+        } else {
+            return io::Error::new(err.kind(), format!("{context}: {err}"));
+        };
+
+        io::Error::new(err.kind(), wrapped)
+    }
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.error)
+    }
+}
+
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Entries and errors from parsing ACL text with
+/// [`parse_reader`](crate::parse_reader).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    /// Entries from the lines that were parsed.
+    pub entries: Vec<AclEntry>,
+
+    /// An [`Error::Parse`] for each line that was skipped, in line order.
+    pub errors: Vec<Error>,
+}
+
+impl ParseReport {
+    /// Return the entries if every line was parsed, otherwise every error.
+    ///
+    /// # Errors
+    ///
+    /// Returns the list of errors if any line was skipped.
+    pub fn into_result(self) -> Result<Vec<AclEntry>, Vec<Error>> {
+        if self.errors.is_empty() {
+            Ok(self.entries)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io() {
+        let err = io::Error::from(Error::UnknownUser("x".to_string()));
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.to_string(), "unknown user name: \"x\"");
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::UnknownUser("x".to_string()))
+        );
+
+        let err = ContextError::wrap("entry 1", &err);
+        let err = ContextError::wrap("Invalid ACL", &err);
+        assert_eq!(
+            err.to_string(),
+            "Invalid ACL: entry 1: unknown user name: \"x\""
+        );
+        assert_eq!(
+            Error::from_io(&err),
+            Some(&Error::UnknownUser("x".to_string()))
+        );
+
+        let err = io::Error::from_raw_os_error(1);
+        assert_eq!(Error::from_io(&err), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let err = Error::MissingRequiredEntry {
+            kind: AclEntryKind::Group,
+            default: true,
+        };
+        assert_eq!(err.to_string(), "missing required default entry \"group\"");
+
+        let err = Error::Os {
+            path: Some(PathBuf::from("/tmp")),
+            errno: 2,
+            func: "acl_get_file".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "File \"/tmp\": No such file or directory (os error 2)"
+        );
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -2,11 +2,24 @@
 
 #![allow(dead_code)]
 
+use crate::error::{ContextError, Error};
+
 use log::debug;
 use std::fmt;
 use std::io;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 
+/// Return an [`io::Error`] for a failed system call.
+fn os_err(errno: i32, func: &str) -> io::Error {
+    Error::Os {
+        path: None,
+        errno,
+        func: func.to_string(),
+    }
+    .into()
+}
+
 /// Log a message and return an [`io::Error`] with the value of errno.
 pub fn log_err<R, T>(ret: R, func: &str, arg: T) -> io::Error
 where
@@ -15,7 +28,7 @@ where
 {
     let err = io::Error::last_os_error();
     debug!("{}({:?}) returned {}, err={}", func, arg, ret, err);
-    err
+    os_err(err.raw_os_error().unwrap_or_default(), func)
 }
 
 /// Log a message and return an [`io::Error`] for a given error code.
@@ -26,7 +39,7 @@ where
     assert!(ret > 0);
     let err = io::Error::from_raw_os_error(ret);
     debug!("{}({:?}) returned {}, err={}", func, arg, ret, err);
-    err
+    os_err(ret, func)
 }
 
 /// Log a message and return an [`io::Result`] with the value of errno.
@@ -46,17 +59,43 @@ where
     Err(log_from_err(ret, func, arg))
 }
 
-/// Return a custom [`io::Result`] with the given message.
-pub fn fail_custom<U>(msg: &str) -> io::Result<U> {
-    Err(io::Error::new(io::ErrorKind::Other, msg))
+/// Return an [`io::Result`] with the given error.
+pub fn fail<U>(err: Error) -> io::Result<U> {
+    Err(err.into())
+}
+
+/// Return the errno value of an [`io::Error`] from a failed system call.
+pub fn errno(err: &io::Error) -> Option<i32> {
+    Error::errno(err)
 }
 
-/// Return a custom [`io::Error`] that prefixes the given error.
-pub fn custom_err(msg: &str, err: &io::Error) -> io::Error {
-    io::Error::new(err.kind(), format!("{msg}: {err}"))
+/// Return an [`io::Error`] that prefixes the given error with context.
+pub fn context_err(context: &str, err: &io::Error) -> io::Error {
+    ContextError::wrap(context, err)
 }
 
-/// Return a custom [`io::Error`] that prefixes the given error with filename.
+/// Return an [`io::Error`] that prefixes the given error with filename.
 pub fn path_err(path: &Path, err: &io::Error) -> io::Error {
-    io::Error::new(err.kind(), format!("File {path:?}: {err}"))
+    // Fill in the path of a failed system call.
+    if let Some(Error::Os {
+        path: None,
+        errno,
+        func,
+    }) = err.get_ref().and_then(|e| e.downcast_ref::<Error>())
+    {
+        return Error::Os {
+            path: Some(path.to_path_buf()),
+            errno: *errno,
+            func: func.clone(),
+        }
+        .into();
+    }
+
+    ContextError::wrap(&format!("File {path:?}"), err)
+}
+
+/// Return an [`io::Error`] that prefixes the given error with file
+/// descriptor.
+pub fn fd_err(fd: RawFd, err: &io::Error) -> io::Error {
+    ContextError::wrap(&format!("File descriptor {fd}"), err)
 }
@@ -191,13 +191,15 @@ impl std::str::FromStr for Flag {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result = Flag::empty();
 
-        for item in s.split(',') {
-            let word = item.trim();
+        for (offset, word) in format::split_fields(s, ',', usize::MAX) {
             if !word.is_empty() {
                 if let Some(flag) = parse_flag_abbreviation(word) {
                     result |= flag;
                 } else {
-                    result |= word.parse::<FlagName>()?.to_flag();
+                    result |= word
+                        .parse::<FlagName>()
+                        .map_err(|err| err.at(offset))?
+                        .to_flag();
                 }
             }
         }
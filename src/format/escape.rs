@@ -0,0 +1,106 @@
+//! Implements octal escapes for names and paths in the text formats.
+
+/// Escape bytes like `getfacl` does: backslash, white space, control
+/// characters, non-ASCII bytes and any byte in `extra` are written as `\ooo`
+/// octal escapes.
+fn escape(bytes: &[u8], extra: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b == b'\\' || !b.is_ascii_graphic() || extra.contains(&b) {
+            result.push('\\');
+            for shift in [6, 3, 0] {
+                result.push(char::from(b'0' + ((b >> shift) & 7)));
+            }
+        } else {
+            result.push(char::from(b));
+        }
+    }
+    result
+}
+
+/// Escape a file name, like `getfacl` does for the `# file:` header.
+pub fn quote(bytes: &[u8]) -> String {
+    escape(bytes, b"")
+}
+
+/// Escape a user or group name. Besides the bytes escaped by [`quote`], this
+/// escapes the separators of the text formats (`:` and `,`) and the comment
+/// character (`#`).
+pub fn quote_name(name: &str) -> String {
+    escape(name.as_bytes(), b":,#")
+}
+
+/// Reverse [`quote`]. Returns None if an escape sequence is invalid.
+pub fn unquote(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            let digits = bytes.get(i + 1..i + 4)?;
+            if !digits.iter().all(|d| (b'0'..=b'7').contains(d)) {
+                return None;
+            }
+            let value = digits
+                .iter()
+                .fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0'));
+            result.push(u8::try_from(value).ok()?);
+            i += 4;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(result)
+}
+
+/// Reverse [`quote_name`]. Returns None if an escape sequence is invalid or
+/// the result is not UTF-8.
+pub fn unquote_name(s: &str) -> Option<String> {
+    String::from_utf8(unquote(s)?).ok()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod escape_tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(b"a b\\c\n\xe9#"), "a\\040b\\134c\\012\\351#");
+        assert_eq!(
+            unquote("a\\040b\\134c\\012\\351#").unwrap(),
+            b"a b\\c\n\xe9#"
+        );
+        assert_eq!(unquote("a\\04"), None);
+        assert_eq!(unquote("a\\999"), None);
+        assert_eq!(unquote("a\\+12"), None);
+        assert_eq!(unquote("a\\777"), None);
+    }
+
+    #[test]
+    fn test_quote_name() {
+        let names = [
+            "DOMAIN\\john smith",
+            "john@corp.example",
+            "a:b,c#d",
+            "jos\u{e9}",
+            "",
+        ];
+        let quoted = [
+            "DOMAIN\\134john\\040smith",
+            "john@corp.example",
+            "a\\072b\\054c\\043d",
+            "jos\\303\\251",
+            "",
+        ];
+
+        for (name, expected) in names.iter().zip(quoted) {
+            assert_eq!(quote_name(name), expected);
+            assert_eq!(unquote_name(expected).as_deref(), Some(*name));
+        }
+
+        assert_eq!(unquote_name("\\377"), None);
+    }
+}
@@ -67,6 +67,29 @@ pub fn read_permname(s: &str) -> Result<PermName> {
 pub enum Error {
     Message(String),
     NotImplemented,
+    At(usize, Box<Error>),
+}
+
+impl Error {
+    /// Return the error at a position `offset` characters further into the
+    /// input. Used when a field is parsed separately from its line.
+    #[must_use]
+    pub fn at(self, offset: usize) -> Error {
+        match self {
+            Error::At(n, err) => Error::At(n + offset, err),
+            err => Error::At(offset, Box::new(err)),
+        }
+    }
+
+    /// Return the position of the error in characters from the start of the
+    /// input.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        match self {
+            Error::At(n, _) => *n,
+            _ => 0,
+        }
+    }
 }
 
 impl ser::Error for Error {
@@ -92,6 +115,7 @@ impl fmt::Display for Error {
         match self {
             Error::Message(msg) => write!(f, "{msg}"),
             Error::NotImplemented => write!(f, "Not implemented"),
+            Error::At(_, err) => write!(f, "{err}"),
         }
     }
 }
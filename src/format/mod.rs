@@ -1,5 +1,7 @@
 //! Implements helper functions for the built-in `AclEntry` format.
 
+mod escape;
+
 #[cfg(feature = "serde")]
 mod format_serde;
 
@@ -17,3 +19,20 @@ pub use format_no_serde::{
     read_aclentrykind, read_flagname, read_permname, write_aclentrykind, write_flagname,
     write_permname, Error,
 };
+
+pub use escape::{quote, quote_name, unquote, unquote_name};
+
+/// Split `s` into at most `limit` fields separated by `sep`. Each field is
+/// trimmed of white space and returned with its offset in characters from the
+/// start of `s`, for use in error positions.
+pub fn split_fields(s: &str, sep: char, limit: usize) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    s.splitn(limit, sep)
+        .map(|field| {
+            let start = field.chars().take_while(|c| c.is_whitespace()).count();
+            let result = (offset + start, field.trim());
+            offset += field.chars().count() + 1;
+            result
+        })
+        .collect()
+}
@@ -0,0 +1,440 @@
+//! Implements uid/gid remapping for user namespaces.
+
+use crate::aclentry::AclEntry;
+use crate::error::Error;
+use crate::failx::{context_err, fail, path_err};
+use crate::qualifier::Qualifier;
+use crate::resolver::{NssResolver, PrincipalResolver};
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Contiguous range of ids, as in a line of `/proc/<pid>/uid_map`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct IdRange {
+    inside: u32,
+    outside: u32,
+    count: u32,
+}
+
+impl IdRange {
+    /// Return the inside id for an outside id, if it is in the range.
+    fn map(self, id: u32) -> Option<u32> {
+        let offset = id.checked_sub(self.outside)?;
+        (offset < self.count).then(|| self.inside + offset)
+    }
+
+    /// Return true if either side of the range overlaps `other`.
+    fn overlaps(self, other: IdRange) -> bool {
+        let overlap = |a: u32, b: u32| {
+            let (a, b) = (u64::from(a), u64::from(b));
+            a < b + u64::from(other.count) && b < a + u64::from(self.count)
+        };
+        overlap(self.inside, other.inside) || overlap(self.outside, other.outside)
+    }
+}
+
+/// Mapping of uids and gids between the host and a user namespace.
+///
+/// Each range maps `count` ids starting at `outside` (the host) to ids
+/// starting at `inside` (the container), like a line of the
+/// `/proc/<pid>/uid_map` and `/proc/<pid>/gid_map` files. Use
+/// [`IdMap::reverse`] to map container ids back to host ids.
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use exacl::IdMap;
+///
+/// let map = IdMap::parse("0 100000 65536\n", "0 100000 65536\n")?;
+/// assert_eq!(map.map_uid(101000), Some(1000));
+/// assert_eq!(map.reverse().map_gid(1000), Some(101000));
+/// assert_eq!(map.map_uid(0), None);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdMap {
+    uids: Vec<IdRange>,
+    gids: Vec<IdRange>,
+}
+
+impl IdMap {
+    /// Construct an empty map. An empty map doesn't map any ids.
+    #[must_use]
+    pub fn new() -> IdMap {
+        IdMap::default()
+    }
+
+    /// Add a range of `count` uids from `outside` to `inside`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the range is empty, doesn't fit in a
+    /// `u32`, or overlaps a range that was already added.
+    pub fn add_uids(&mut self, inside: u32, outside: u32, count: u32) -> io::Result<()> {
+        add_range(&mut self.uids, inside, outside, count)
+            .or_else(|message| fail(Error::InvalidInput(message)))
+    }
+
+    /// Add a range of `count` gids from `outside` to `inside`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the range is empty, doesn't fit in a
+    /// `u32`, or overlaps a range that was already added.
+    pub fn add_gids(&mut self, inside: u32, outside: u32, count: u32) -> io::Result<()> {
+        add_range(&mut self.gids, inside, outside, count)
+            .or_else(|message| fail(Error::InvalidInput(message)))
+    }
+
+    /// Construct a map from the text of a `uid_map` and a `gid_map` file.
+    ///
+    /// Each line has three numbers separated by whitespace: the first id
+    /// inside the namespace, the first id outside, and the length of the
+    /// range. Blank lines are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a line is malformed or a range is not
+    /// valid.
+    pub fn parse(uid_map: &str, gid_map: &str) -> io::Result<IdMap> {
+        Ok(IdMap {
+            uids: parse_ranges(uid_map).map_err(|err| context_err("uid_map", &err))?,
+            gids: parse_ranges(gid_map).map_err(|err| context_err("gid_map", &err))?,
+        })
+    }
+
+    /// Construct a map by reading a `uid_map` and a `gid_map` file.
+    ///
+    /// To use the mapping of a running container, read
+    /// `/proc/<pid>/uid_map` and `/proc/<pid>/gid_map`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a file can't be read or has a malformed
+    /// line.
+    pub fn read<P, Q>(uid_map: P, gid_map: Q) -> io::Result<IdMap>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Ok(IdMap {
+            uids: read_ranges(uid_map.as_ref())?,
+            gids: read_ranges(gid_map.as_ref())?,
+        })
+    }
+
+    /// Return the mapping in the other direction, from inside to outside.
+    #[must_use]
+    pub fn reverse(&self) -> IdMap {
+        let reverse = |ranges: &[IdRange]| {
+            ranges
+                .iter()
+                .map(|r| IdRange {
+                    inside: r.outside,
+                    outside: r.inside,
+                    count: r.count,
+                })
+                .collect()
+        };
+
+        IdMap {
+            uids: reverse(&self.uids),
+            gids: reverse(&self.gids),
+        }
+    }
+
+    /// Map a uid from outside to inside. Return `None` if it isn't mapped.
+    #[must_use]
+    pub fn map_uid(&self, uid: u32) -> Option<u32> {
+        self.uids.iter().find_map(|r| r.map(uid))
+    }
+
+    /// Map a gid from outside to inside. Return `None` if it isn't mapped.
+    #[must_use]
+    pub fn map_gid(&self, gid: u32) -> Option<u32> {
+        self.gids.iter().find_map(|r| r.map(gid))
+    }
+}
+
+/// Add a range to a list of ranges. Return an error message if the range is
+/// not valid.
+fn add_range(
+    ranges: &mut Vec<IdRange>,
+    inside: u32,
+    outside: u32,
+    count: u32,
+) -> Result<(), String> {
+    let range = IdRange {
+        inside,
+        outside,
+        count,
+    };
+
+    if count == 0 {
+        return Err(format!("empty id range: {inside} {outside} {count}"));
+    }
+
+    let end = u64::from(inside.max(outside)) + u64::from(count);
+    if end > u64::from(u32::MAX) + 1 {
+        return Err(format!(
+            "id range out of bounds: {inside} {outside} {count}"
+        ));
+    }
+
+    if ranges.iter().any(|r| r.overlaps(range)) {
+        return Err(format!("overlapping id range: {inside} {outside} {count}"));
+    }
+
+    ranges.push(range);
+    Ok(())
+}
+
+/// Parse the text of a `uid_map` or `gid_map` file.
+fn parse_ranges(text: &str) -> io::Result<Vec<IdRange>> {
+    let mut ranges = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let parse_err = |column: usize, message: String| Error::Parse {
+            line: i + 1,
+            column: column + 1,
+            message,
+        };
+
+        let fields = split_whitespace(line);
+        if fields.is_empty() {
+            continue;
+        }
+
+        let mut ids = [0; 3];
+        if fields.len() != ids.len() {
+            return fail(parse_err(
+                0,
+                format!("expected 3 fields, found {}", fields.len()),
+            ));
+        }
+
+        for (id, (column, field)) in ids.iter_mut().zip(fields) {
+            *id = field
+                .parse::<u32>()
+                .or_else(|_| fail(parse_err(column, format!("invalid number: {field:?}"))))?;
+        }
+
+        let [inside, outside, count] = ids;
+        add_range(&mut ranges, inside, outside, count)
+            .or_else(|message| fail(parse_err(0, message)))?;
+    }
+
+    Ok(ranges)
+}
+
+/// Read and parse a `uid_map` or `gid_map` file.
+fn read_ranges(path: &Path) -> io::Result<Vec<IdRange>> {
+    fs::read_to_string(path)
+        .and_then(|text| parse_ranges(&text))
+        .map_err(|err| path_err(path, &err))
+}
+
+/// Split a line into fields separated by whitespace. Return each field with
+/// its offset in characters, starting at 0.
+fn split_whitespace(line: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    let mut start = None;
+
+    let chars = line.char_indices().chain([(line.len(), ' ')]);
+    for (column, (i, ch)) in chars.enumerate() {
+        match (ch.is_whitespace(), start) {
+            (true, Some((col, s))) => {
+                fields.push((col, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some((column, i)),
+            _ => (),
+        }
+    }
+
+    fields
+}
+
+/// Remap the uid/gid of named user and group entries.
+///
+/// Each named user or group is converted to a uid/gid and mapped from outside
+/// to inside using `map`. The returned entries name users and groups by
+/// their decimal uid/gid, since the mapped ids may not exist on this host;
+/// use [`AclOption::NUMERIC_IDS`](crate::AclOption::NUMERIC_IDS) to apply
+/// them. Use [`IdMap::reverse`] to map the other way.
+///
+/// Names that aren't decimal ids are looked up in the system's user and group
+/// database; use [`remap_ids_with`] to look them up elsewhere. To remap
+/// entries without any name lookups, get them with `NUMERIC_IDS` or use
+/// [`xattr::remap_ids`](crate::xattr::remap_ids) on the extended attribute
+/// value.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a name can't be resolved, or an id isn't
+/// mapped.
+pub fn remap_ids(entries: &[AclEntry], map: &IdMap) -> io::Result<Vec<AclEntry>> {
+    remap_ids_with(entries, map, &NssResolver)
+}
+
+/// Remap the uid/gid of named user and group entries, using `resolver` to
+/// convert user/group names to uid/gid.
+///
+/// Works like [`remap_ids`], which uses [`NssResolver`](crate::NssResolver).
+/// Use a [`FileResolver`](crate::FileResolver) to look up names in the
+/// `passwd` and `group` files of a container image.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a name can't be resolved, or an id isn't
+/// mapped.
+pub fn remap_ids_with<R>(
+    entries: &[AclEntry],
+    map: &IdMap,
+    resolver: &R,
+) -> io::Result<Vec<AclEntry>>
+where
+    R: PrincipalResolver + ?Sized,
+{
+    let mut result = entries.to_vec();
+
+    for entry in &mut result {
+        if entry.name.is_empty() {
+            continue;
+        }
+
+        let id = match entry.qualifier(&resolver)? {
+            Qualifier::User(uid) => map.map_uid(uid).map_or_else(
+                || fail(Error::InvalidEntry(format!("unmapped uid: {uid}"))),
+                Ok,
+            )?,
+            Qualifier::Group(gid) => map.map_gid(gid).map_or_else(
+                || fail(Error::InvalidEntry(format!("unmapped gid: {gid}"))),
+                Ok,
+            )?,
+            _ => continue,
+        };
+        entry.name = id.to_string();
+        entry.id = Some(id);
+    }
+
+    Ok(result)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod idmap_tests {
+    use super::*;
+
+    #[test]
+    fn test_idmap() {
+        let mut map = IdMap::new();
+        assert_eq!(map.map_uid(0), None);
+
+        map.add_uids(0, 100_000, 1000).unwrap();
+        map.add_uids(1000, 5000, 1).unwrap();
+        map.add_gids(0, 200_000, 10).unwrap();
+
+        assert_eq!(map.map_uid(100_000), Some(0));
+        assert_eq!(map.map_uid(100_999), Some(999));
+        assert_eq!(map.map_uid(101_000), None);
+        assert_eq!(map.map_uid(5000), Some(1000));
+        assert_eq!(map.map_gid(200_009), Some(9));
+        assert_eq!(map.map_gid(100_000), None);
+
+        let reverse = map.reverse();
+        assert_eq!(reverse.map_uid(999), Some(100_999));
+        assert_eq!(reverse.map_uid(1000), Some(5000));
+        assert_eq!(reverse.reverse(), map);
+
+        let err = map.add_uids(500, 6000, 1).unwrap_err();
+        assert_eq!(err.to_string(), "overlapping id range: 500 6000 1");
+
+        let err = map.add_uids(2000, 4999, 2).unwrap_err();
+        assert_eq!(err.to_string(), "overlapping id range: 2000 4999 2");
+
+        let err = map.add_gids(10, 0, 0).unwrap_err();
+        assert_eq!(err.to_string(), "empty id range: 10 0 0");
+
+        let err = map.add_gids(10, u32::MAX, 2).unwrap_err();
+        assert_eq!(err.to_string(), "id range out of bounds: 10 4294967295 2");
+
+        map.add_gids(10, u32::MAX, 1).unwrap();
+        assert_eq!(map.map_gid(u32::MAX), Some(10));
+    }
+
+    #[test]
+    fn test_idmap_parse() {
+        let map = IdMap::parse(
+            "         0     100000      65536\n\n     65536       1000          1\n",
+            "0 100000 65536",
+        )
+        .unwrap();
+        assert_eq!(map.map_uid(100_000), Some(0));
+        assert_eq!(map.map_uid(1000), Some(65536));
+        assert_eq!(map.map_gid(1000), None);
+
+        let err = IdMap::parse("0 1 2\n0 100000\n", "").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "uid_map: line 2, column 1: expected 3 fields, found 2"
+        );
+
+        let err = IdMap::parse("", "0  x1 2\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "gid_map: line 1, column 4: invalid number: \"x1\""
+        );
+
+        // Columns count characters, not bytes.
+        let err = IdMap::parse("", "0\u{a0}é 2\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "gid_map: line 1, column 3: invalid number: \"é\""
+        );
+
+        let err = IdMap::parse("0 1 2\n1 5 1\n", "").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "uid_map: line 2, column 1: overlapping id range: 1 5 1"
+        );
+    }
+
+    #[test]
+    fn test_remap_ids() {
+        let mut map = IdMap::new();
+        map.add_uids(0, 100_000, 65536).unwrap();
+        map.add_gids(0, 200_000, 65536).unwrap();
+
+        let entries = crate::from_str(
+            "u::rw
+            u:101000:r
+            g::r
+            g:201000:r
+            d:u:100500:rwx
+            m::r
+            o::",
+        )
+        .unwrap();
+
+        let result = remap_ids(&entries, &map).unwrap();
+        assert_eq!(
+            crate::to_string(&result).unwrap(),
+            "allow::user::read,write
+allow::user:1000:read
+allow::group::read
+allow::group:1000:read
+allow:default:user:500:read,write,execute
+allow::mask::read
+allow::other::
+"
+        );
+
+        assert_eq!(remap_ids(&result, &map.reverse()).unwrap(), entries);
+
+        let err = remap_ids(&result, &map).unwrap_err();
+        assert_eq!(err.to_string(), "unmapped uid: 1000");
+    }
+}
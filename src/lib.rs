@@ -33,6 +33,21 @@
 //! - [`getfacl`] retrieves the ACL for a file or directory.
 //! - [`setfacl`] sets the ACL for files or directories.
 //!
+//! [`getfacl_fd`] and [`setfacl_fd`] do the same for an open file descriptor.
+//! [`getfacl_with`] and [`setfacl_with`] take a [`PrincipalResolver`] to
+//! convert between user/group names and uid/gid. Other functions that look up
+//! names have a `_with` variant too.
+//! [`getfacl_recursive`] and [`setfacl_recursive`] walk a directory tree.
+//!
+//! On Linux and `FreeBSD`, [`to_posix_text`] and [`from_posix_text`] convert
+//! between a list of entries and the text form used by GNU `getfacl`.
+//! [`to_short_text`] writes the comma-separated short form.
+//! [`dump_tree`] and [`restore`] save and restore the ACL's of a directory
+//! tree, like `getfacl -R` and `setfacl --restore`.
+//!
+//! On Linux, the `xattr` module converts between a list of entries and the
+//! binary value of the `system.posix_acl_*` extended attributes.
+//!
 //! On Linux and `FreeBSD`, the ACL contains entries for the default ACL, if
 //! present.
 //!
@@ -51,32 +66,116 @@
 #![warn(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod access;
 mod acl;
 mod aclentry;
 mod bindings;
 mod bititer;
+mod diff;
+mod document;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod dump;
+mod error;
 mod failx;
 mod flag;
 mod format;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod idmap;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod normalize;
 mod perm;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod posix_text;
+mod principal;
 mod qualifier;
+mod recursive;
+mod resolver;
 mod sys;
 mod unix;
 mod util;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+mod validate;
 
-// Export AclOption, AclEntry, AclEntryKind, Flag and Perm.
+#[cfg(target_os = "linux")]
+#[cfg_attr(docsrs, doc(cfg(target_os = "linux")))]
+pub mod xattr;
+
+// Export AclOption, AclEntry, AclEntryKind, AclDiff, AclDocument, Error, Flag,
+// ParseReport, Perm, Principal, RecursiveOption and the principal resolvers.
 pub use acl::AclOption;
 pub use aclentry::{AclEntry, AclEntryKind};
+pub use diff::{apply, diff, AclDiff, AclEntryChange};
+pub use document::{AclDocument, FileKind};
+pub use error::{Error, ParseReport};
 pub use flag::Flag;
 pub use perm::Perm;
+pub use principal::Principal;
+pub use recursive::{
+    getfacl_recursive, getfacl_recursive_with, setfacl_recursive, setfacl_recursive_with,
+    RecursiveOption,
+};
+pub use resolver::{CachingResolver, FileResolver, NssResolver, PrincipalResolver};
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use access::{check_access, check_access_with, effective_perms, Credentials};
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use dump::{dump_tree, dump_tree_with, restore, restore_with};
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use idmap::{remap_ids, remap_ids_with, IdMap};
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use normalize::{normalize, normalize_with};
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use posix_text::{from_posix_text, to_posix_text, to_short_text, TextOption};
+
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub use validate::{validate, validate_with, ValidateOption, ValidationIssue, ValidationIssueKind};
 
 use acl::Acl;
-use failx::custom_err;
+use failx::context_err;
 use std::io::{self, BufRead};
+use std::os::unix::io::{AsFd, BorrowedFd};
 use std::path::Path;
 
 #[cfg(not(target_os = "macos"))]
-use failx::fail_custom;
+use failx::fail;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use qualifier::Qualifier;
+
+/// File whose ACL is read or written: a path or an open file descriptor.
+#[derive(Clone, Copy)]
+enum AclTarget<'a> {
+    Path(&'a Path),
+    Fd(BorrowedFd<'a>),
+}
+
+impl AclTarget<'_> {
+    /// Read the ACL of the file.
+    fn read(self, options: AclOption) -> io::Result<Acl> {
+        match self {
+            AclTarget::Path(path) => Acl::read(path, options),
+            AclTarget::Fd(fd) => Acl::read_fd(fd, options),
+        }
+    }
+
+    /// Write `acl` to the file.
+    fn write(self, acl: &Acl, options: AclOption) -> io::Result<()> {
+        match self {
+            AclTarget::Path(path) => acl.write(path, options),
+            AclTarget::Fd(fd) => acl.write_fd(fd, options),
+        }
+    }
+}
 
 /// Get access control list (ACL) for a file or directory.
 ///
@@ -131,30 +230,64 @@ where
     P: AsRef<Path>,
     O: Into<Option<AclOption>>,
 {
-    _getfacl(path.as_ref(), options.into().unwrap_or_default())
+    getfacl_with(path, options, &NssResolver)
+}
+
+/// Get access control list (ACL) for a file or directory, using `resolver` to
+/// convert uid/gid to user/group names.
+///
+/// Works like [`getfacl`], which uses [`NssResolver`]. With
+/// [`AclOption::NUMERIC_IDS`], names are not looked up and `resolver` is not
+/// used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn getfacl_with<P, O, R>(path: P, options: O, resolver: &R) -> io::Result<Vec<AclEntry>>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    _getfacl(
+        AclTarget::Path(path.as_ref()),
+        options.into().unwrap_or_default(),
+        &resolver,
+    )
 }
 
 #[cfg(target_os = "macos")]
-fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
-    Acl::read(path, options)?.entries()
+fn _getfacl(
+    target: AclTarget,
+    options: AclOption,
+    resolver: &dyn PrincipalResolver,
+) -> io::Result<Vec<AclEntry>> {
+    target
+        .read(options)?
+        .entries(resolver::for_options(options, resolver))
 }
 
 #[cfg(not(target_os = "macos"))]
-fn _getfacl(path: &Path, options: AclOption) -> io::Result<Vec<AclEntry>> {
+fn _getfacl(
+    target: AclTarget,
+    options: AclOption,
+    resolver: &dyn PrincipalResolver,
+) -> io::Result<Vec<AclEntry>> {
+    let resolver = resolver::for_options(options, resolver);
     if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")
+        fail(Error::InvalidInput(
+            "ACCESS_ACL and DEFAULT_ACL are mutually exclusive options".to_string(),
+        ))
     } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        Acl::read(path, options)?.entries()
+        target.read(options)?.entries(resolver)
     } else {
-        let acl = Acl::read(path, options)?;
-        let mut entries = acl.entries()?;
+        let acl = target.read(options)?;
+        let mut entries = acl.entries(resolver)?;
 
         if acl.is_posix() {
-            let mut default = Acl::read(
-                path,
-                options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
-            )?
-            .entries()?;
+            let mut default = target
+                .read(options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR)?
+                .entries(resolver)?;
 
             entries.append(&mut default);
         }
@@ -233,62 +366,225 @@ where
     P: AsRef<Path>,
     O: Into<Option<AclOption>>,
 {
-    _setfacl(paths, entries, options.into().unwrap_or_default())
+    setfacl_with(paths, entries, options, &NssResolver)
 }
 
-#[cfg(target_os = "macos")]
-fn _setfacl<P>(paths: &[P], entries: &[AclEntry], options: AclOption) -> io::Result<()>
+/// Set access control list (ACL) for specified files and directories, using
+/// `resolver` to convert user/group names to uid/gid.
+///
+/// Works like [`setfacl`], which uses [`NssResolver`]. With
+/// [`AclOption::NUMERIC_IDS`], names must be decimal uid/gid and `resolver` is
+/// not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_with<P, O, R>(
+    paths: &[P],
+    entries: &[AclEntry],
+    options: O,
+    resolver: &R,
+) -> io::Result<()>
 where
     P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
 {
-    let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
-    for path in paths {
-        acl.write(path.as_ref(), options)?;
+    let targets: Vec<AclTarget> = paths
+        .iter()
+        .map(|path| AclTarget::Path(path.as_ref()))
+        .collect();
+    _setfacl(
+        &targets,
+        entries,
+        options.into().unwrap_or_default(),
+        &resolver,
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn _setfacl(
+    targets: &[AclTarget],
+    entries: &[AclEntry],
+    options: AclOption,
+    resolver: &dyn PrincipalResolver,
+) -> io::Result<()> {
+    let resolver = resolver::for_options(options, resolver);
+    let acl =
+        Acl::from_entries(entries, resolver).map_err(|err| context_err("Invalid ACL", &err))?;
+    for target in targets {
+        target.write(&acl, options)?;
     }
 
     Ok(())
 }
 
 #[cfg(not(target_os = "macos"))]
-fn _setfacl<P>(paths: &[P], entries: &[AclEntry], options: AclOption) -> io::Result<()>
-where
-    P: AsRef<Path>,
-{
+fn _setfacl(
+    targets: &[AclTarget],
+    entries: &[AclEntry],
+    options: AclOption,
+    resolver: &dyn PrincipalResolver,
+) -> io::Result<()> {
+    let resolver = resolver::for_options(options, resolver);
     if options.contains(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        fail_custom("ACCESS_ACL and DEFAULT_ACL are mutually exclusive options")?;
+        fail(Error::InvalidInput(
+            "ACCESS_ACL and DEFAULT_ACL are mutually exclusive options".to_string(),
+        ))?;
     } else if options.intersects(AclOption::ACCESS_ACL | AclOption::DEFAULT_ACL) {
-        let acl = Acl::from_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+        let acl =
+            Acl::from_entries(entries, resolver).map_err(|err| context_err("Invalid ACL", &err))?;
 
-        for path in paths {
-            acl.write(path.as_ref(), options)?;
+        for target in targets {
+            target.write(&acl, options)?;
         }
     } else {
-        let (access_acl, default_acl) =
-            Acl::from_unified_entries(entries).map_err(|err| custom_err("Invalid ACL", &err))?;
+        let (access_acl, default_acl) = Acl::from_unified_entries(entries, resolver)
+            .map_err(|err| context_err("Invalid ACL", &err))?;
 
         if access_acl.is_empty() {
-            fail_custom("Invalid ACL: missing required entries")?;
+            let err = Error::InvalidInput("missing required entries".to_string());
+            return Err(context_err("Invalid ACL", &err.into()));
         }
 
-        for path in paths {
-            let path = path.as_ref();
+        for target in targets {
             if access_acl.is_posix() {
                 // Try to set default acl first. This will fail if path is not
                 // a directory and default_acl is non-empty. This ordering
                 // avoids leaving the file's ACL in a partially changed state
                 // after an error (simply because it was a non-directory).
-                default_acl.write(
-                    path,
+                target.write(
+                    &default_acl,
                     options | AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
                 )?;
             }
-            access_acl.write(path, options)?;
+            target.write(&access_acl, options)?;
         }
     }
 
     Ok(())
 }
 
+/// Get access control list (ACL) for an open file descriptor.
+///
+/// This function works like [`getfacl`], except that it reads the ACL of an
+/// open file (e.g. a [`std::fs::File`]) instead of a path. Use it to avoid a
+/// race between checking a file and getting or setting its ACL.
+///
+/// [`AclOption::SYMLINK_ACL`] is ignored.
+///
+/// # Linux
+///
+/// The default ACL is accessed through the `/proc/self/fd` file system, which
+/// must be mounted.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::getfacl_fd;
+/// use std::fs::File;
+///
+/// let file = File::open("./tmp/foo")?;
+/// let entries = getfacl_fd(&file, None)?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn getfacl_fd<F, O>(file: F, options: O) -> io::Result<Vec<AclEntry>>
+where
+    F: AsFd,
+    O: Into<Option<AclOption>>,
+{
+    getfacl_fd_with(file, options, &NssResolver)
+}
+
+/// Get access control list (ACL) for an open file descriptor, using
+/// `resolver` to convert uid/gid to user/group names.
+///
+/// Works like [`getfacl_fd`], which uses [`NssResolver`]. With
+/// [`AclOption::NUMERIC_IDS`], names are not looked up and `resolver` is not
+/// used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn getfacl_fd_with<F, O, R>(file: F, options: O, resolver: &R) -> io::Result<Vec<AclEntry>>
+where
+    F: AsFd,
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    _getfacl(
+        AclTarget::Fd(file.as_fd()),
+        options.into().unwrap_or_default(),
+        &resolver,
+    )
+}
+
+/// Set access control list (ACL) for an open file descriptor.
+///
+/// This function works like [`setfacl`], except that it sets the ACL of an
+/// open file (e.g. a [`std::fs::File`]) instead of a list of paths.
+///
+/// [`AclOption::SYMLINK_ACL`] is ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use exacl::{getfacl_fd, setfacl_fd, AclEntry, Perm};
+/// use std::fs::File;
+///
+/// let file = File::open("./tmp/foo")?;
+/// let mut entries = getfacl_fd(&file, None)?;
+/// entries.push(AclEntry::allow_user("some_user", Perm::READ, None));
+/// setfacl_fd(&file, &entries, None)?;
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_fd<F, O>(file: F, entries: &[AclEntry], options: O) -> io::Result<()>
+where
+    F: AsFd,
+    O: Into<Option<AclOption>>,
+{
+    setfacl_fd_with(file, entries, options, &NssResolver)
+}
+
+/// Set access control list (ACL) for an open file descriptor, using
+/// `resolver` to convert user/group names to uid/gid.
+///
+/// Works like [`setfacl_fd`], which uses [`NssResolver`]. With
+/// [`AclOption::NUMERIC_IDS`], names must be decimal uid/gid and `resolver` is
+/// not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_fd_with<F, O, R>(
+    file: F,
+    entries: &[AclEntry],
+    options: O,
+    resolver: &R,
+) -> io::Result<()>
+where
+    F: AsFd,
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    _setfacl(
+        &[AclTarget::Fd(file.as_fd())],
+        entries,
+        options.into().unwrap_or_default(),
+        &resolver,
+    )
+}
+
 /// Write ACL entries to text.
 ///
 /// Each ACL entry is printed on a separate line. The five fields are separated
@@ -306,6 +602,11 @@ where
 ///
 /// Each record, including the last, is terminated by a final newline.
 ///
+/// Special characters in `<name>` are written as `\ooo` octal escapes, like
+/// `getfacl` does: backslash, white space, `:`, `,`, `#`, control characters
+/// and non-ASCII bytes. For example, `DOMAIN\john smith` is written as
+/// `DOMAIN\134john\040smith`.
+///
 /// # Sample Output
 ///
 /// ```text
@@ -343,11 +644,16 @@ pub fn to_writer<W: io::Write>(mut writer: W, entries: &[AclEntry]) -> io::Resul
 ///   <perms> - comma-separated list of permissions
 /// ```
 ///
-/// Supported flags and permissions vary by platform.
+/// Supported flags and permissions vary by platform. Octal escapes in
+/// `<name>`, as written by `to_writer`, are decoded.
 ///
 /// Supported abbreviations:  d = default, r = read, w = write, x = execute,
 /// u = user, g = group, o = other, m = mask
 ///
+/// Entries are not split on commas, since a comma separates permissions. To
+/// read the comma-separated short text form (`u::rw-,g::r--,o::---`), use
+/// `from_posix_text`.
+///
 /// # Sample Input
 ///
 /// ```text
@@ -359,23 +665,82 @@ pub fn to_writer<W: io::Write>(mut writer: W, entries: &[AclEntry]) -> io::Resul
 ///
 /// # Errors
 ///
-/// Returns an [`io::Error`] on failure.
+/// Returns an [`io::Error`] on failure. If a line can't be parsed, the error
+/// wraps an [`Error::Parse`] with the line and column of the bad field. Use
+/// [`parse_reader`] to report every bad line.
 pub fn from_reader<R: io::Read>(reader: R) -> io::Result<Vec<AclEntry>> {
     let mut result = Vec::<AclEntry>::new();
     let buf = io::BufReader::new(reader);
 
-    for line_result in buf.lines() {
+    for (i, line_result) in buf.lines().enumerate() {
         let line = line_result?;
-
-        let src_line = trim_comment(&line).trim();
-        if !src_line.is_empty() {
-            result.push(src_line.parse::<AclEntry>()?);
+        if let Some(entry) = parse_entry_line(&line, i + 1) {
+            result.push(entry?);
         }
     }
 
     Ok(result)
 }
 
+/// Read ACL entries from text, and report every line that can't be parsed.
+///
+/// The format is the same as [`from_reader`]. Instead of stopping at the
+/// first bad line, every line is parsed. The report holds the entries from
+/// the lines that were parsed, and an [`Error::Parse`] with the line, column
+/// and message for each line that was skipped.
+///
+/// To be lenient, use the entries and log the errors. To be strict but see
+/// every problem at once, use [`ParseReport::into_result`].
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let report = exacl::parse_reader(&b"u:admin:rw\ng:staff:wrte\n"[..])?;
+/// assert_eq!(report.entries.len(), 1);
+/// assert_eq!(
+///     report.errors[0].to_string(),
+///     "line 2, column 9: unknown variant `wrte`, expected one of `read`, `write`, `execute`"
+/// );
+/// # Ok(()) }
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the text can't be read.
+pub fn parse_reader<R: io::Read>(reader: R) -> io::Result<ParseReport> {
+    let mut report = ParseReport::default();
+    let buf = io::BufReader::new(reader);
+
+    for (i, line_result) in buf.lines().enumerate() {
+        let line = line_result?;
+        match parse_entry_line(&line, i + 1) {
+            Some(Ok(entry)) => report.entries.push(entry),
+            Some(Err(err)) => report.errors.push(err),
+            None => (),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Parse one line of text into an entry. Returns None if the line is empty or
+/// only has a comment.
+fn parse_entry_line(line: &str, lineno: usize) -> Option<Result<AclEntry, Error>> {
+    let src_line = trim_comment(line);
+    let indent = src_line.chars().take_while(|c| c.is_whitespace()).count();
+    let src_line = src_line.trim();
+    if src_line.is_empty() {
+        return None;
+    }
+
+    Some(src_line.parse::<AclEntry>().map_err(|err| Error::Parse {
+        line: lineno,
+        column: indent + err.offset() + 1,
+        message: err.to_string(),
+    }))
+}
+
 /// Return line with end of line comment removed.
 fn trim_comment(line: &str) -> &str {
     line.find('#').map_or(line, |n| &line[0..n])
@@ -421,3 +786,440 @@ pub fn from_mode(mode: u32) -> Vec<AclEntry> {
         AclEntry::allow_other(Perm::from_bits_truncate(mode & 7), None),
     ]
 }
+
+/// Remove the extended entries from the access ACL of a file or directory.
+///
+/// Reduces the access ACL to the minimal entries for the owner, owning group
+/// and other. Named users, named groups and the mask are removed. Like
+/// `setfacl -b`, the owning group's permissions are limited by the old mask,
+/// so removing the extended entries never grants more access. The default
+/// ACL is not changed; use [`remove_default_acl`].
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn remove_extended_acl<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut entries = getfacl(path, AclOption::ACCESS_ACL)?;
+    let mask = entries
+        .iter()
+        .find(|entry| entry.kind == AclEntryKind::Mask)
+        .map_or_else(Perm::all, |entry| entry.perms);
+
+    entries.retain(|entry| match entry.kind {
+        AclEntryKind::User | AclEntryKind::Group => entry.name.is_empty(),
+        AclEntryKind::Other => true,
+        _ => false,
+    });
+
+    for entry in &mut entries {
+        if entry.kind == AclEntryKind::Group {
+            entry.perms &= mask;
+        }
+    }
+
+    setfacl(&[path], &entries, AclOption::ACCESS_ACL)
+}
+
+/// Remove the default ACL of a directory, like `setfacl -k`.
+///
+/// Does nothing if `path` is not a directory.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn remove_default_acl<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    setfacl(
+        &[path],
+        &[],
+        AclOption::DEFAULT_ACL | AclOption::IGNORE_EXPECTED_FILE_ERR,
+    )
+}
+
+/// Add or update entries in the ACL of a file or directory, like `setfacl -m`.
+///
+/// Reads the current ACL, replaces the entries that match the given entries'
+/// principal (see [`Principal`]), appends the rest, and writes the result back.
+/// Names are resolved before matching, so "500" matches an existing entry for
+/// the user with uid 500.
+///
+/// The mask is recomputed for each ACL that changes, unless `entries` includes
+/// a mask entry for that ACL.
+///
+/// Use [`AclOption::ACCESS_ACL`] or [`AclOption::DEFAULT_ACL`] to only read and
+/// write one of the ACL's. With [`AclOption::DEFAULT_ACL`], all entries are
+/// treated as default entries.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn modify_acl<P, O>(path: P, entries: &[AclEntry], options: O) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    modify_acl_with(path, entries, options, &NssResolver)
+}
+
+/// Add or update entries in the ACL of a file or directory, using `resolver`
+/// to convert between user/group names and uid/gid.
+///
+/// Works like [`modify_acl`], which uses [`NssResolver`]. With
+/// [`AclOption::NUMERIC_IDS`], names must be decimal uid/gid and `resolver` is
+/// not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn modify_acl_with<P, O, R>(
+    path: P,
+    entries: &[AclEntry],
+    options: O,
+    resolver: &R,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let path = path.as_ref();
+    let options = options.into().unwrap_or_default();
+    let resolver = resolver::for_options(options, &resolver);
+
+    let mut acl = getfacl_with(path, options, resolver)?;
+    let mut keys = acl_keys(&acl, options, resolver);
+    let mut recompute_mask = [false; 2];
+    let mut keep_mask = [false; 2];
+
+    for entry in entries {
+        let key = principal_key(entry.qualifier(resolver)?, entry.flags, options);
+        let is_default = usize::from(key.1);
+
+        let mut entry = entry.clone();
+        if key.1 {
+            entry.flags |= Flag::DEFAULT;
+        }
+
+        if entry.kind == AclEntryKind::Mask {
+            keep_mask[is_default] = true;
+        } else {
+            recompute_mask[is_default] = true;
+        }
+
+        if let Some(i) = keys.iter().position(|k| k.as_ref() == Some(&key)) {
+            acl[i] = entry;
+        } else {
+            acl.push(entry);
+            keys.push(Some(key));
+        }
+    }
+
+    for (i, recompute) in recompute_mask.into_iter().enumerate() {
+        if recompute && !keep_mask[i] {
+            remove_mask(&mut acl, i == 1);
+        }
+    }
+
+    setfacl_with(&[path], &acl, options, resolver)
+}
+
+/// Remove entries from the ACL of a file or directory, like `setfacl -x`.
+///
+/// Reads the current ACL, removes the entries that match the given principals,
+/// and writes the result back. Names are resolved before matching. The mask is
+/// recomputed for each ACL that changes.
+///
+/// Use [`AclOption::ACCESS_ACL`] or [`AclOption::DEFAULT_ACL`] to only read and
+/// write one of the ACL's. With [`AclOption::DEFAULT_ACL`], all principals are
+/// treated as default entries.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn remove_acl_entries<P, O>(path: P, principals: &[Principal], options: O) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+{
+    remove_acl_entries_with(path, principals, options, &NssResolver)
+}
+
+/// Remove entries from the ACL of a file or directory, using `resolver` to
+/// convert between user/group names and uid/gid.
+///
+/// Works like [`remove_acl_entries`], which uses [`NssResolver`]. With
+/// [`AclOption::NUMERIC_IDS`], names must be decimal uid/gid and `resolver` is
+/// not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn remove_acl_entries_with<P, O, R>(
+    path: P,
+    principals: &[Principal],
+    options: O,
+    resolver: &R,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let path = path.as_ref();
+    let options = options.into().unwrap_or_default();
+    let resolver = resolver::for_options(options, &resolver);
+
+    let mut acl = getfacl_with(path, options, resolver)?;
+    let remove = principals
+        .iter()
+        .map(|principal| {
+            let qualifier = principal.qualifier(resolver)?;
+            Ok(principal_key(qualifier, principal.flags, options))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut recompute_mask = [false; 2];
+    let mut keys = acl_keys(&acl, options, resolver).into_iter();
+    acl.retain(|_| match keys.next().flatten() {
+        Some(key) if remove.contains(&key) => {
+            recompute_mask[usize::from(key.1)] = true;
+            false
+        }
+        _ => true,
+    });
+
+    for (i, recompute) in recompute_mask.into_iter().enumerate() {
+        if recompute {
+            remove_mask(&mut acl, i == 1);
+        }
+    }
+
+    setfacl_with(&[path], &acl, options, resolver)
+}
+
+/// Return the key used to match a principal with the entries in an ACL.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+const fn principal_key(qualifier: Qualifier, flags: Flag, options: AclOption) -> (Qualifier, bool) {
+    let is_default = flags.contains(Flag::DEFAULT) || options.contains(AclOption::DEFAULT_ACL);
+
+    (qualifier, is_default)
+}
+
+/// Return the keys for the entries in an ACL. Entries that can't be matched
+/// have no key.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn acl_keys(
+    entries: &[AclEntry],
+    options: AclOption,
+    resolver: &dyn PrincipalResolver,
+) -> Vec<Option<(Qualifier, bool)>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let qualifier = entry.qualifier(resolver).ok()?;
+            Some(principal_key(qualifier, entry.flags, options))
+        })
+        .collect()
+}
+
+/// Remove the mask entry from the access or default ACL. A new mask is
+/// computed when the ACL is written.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn remove_mask(entries: &mut Vec<AclEntry>, is_default: bool) {
+    entries.retain(|entry| {
+        entry.kind != AclEntryKind::Mask || entry.flags.contains(Flag::DEFAULT) != is_default
+    });
+}
+
+/// Update an ACL to match the traditional `mode` permission bits.
+///
+/// This does what `chmod` does to a file's access ACL. The owner and other
+/// entries are set from the owner and other bits of `mode`. If the ACL has a
+/// mask entry, the group bits set the mask and the owning group entry is left
+/// unchanged. Otherwise, the group bits set the owning group entry. If the ACL
+/// needs a mask but doesn't have one, a mask entry is added.
+///
+/// Entries for the default ACL are not changed. Extra bits outside the mask
+/// 0o777 are ignored.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+#[must_use]
+pub fn apply_mode(entries: &[AclEntry], mode: u32) -> Vec<AclEntry> {
+    let filter = (Flag::empty(), Flag::DEFAULT);
+    let mut result = entries.to_vec();
+
+    if Acl::compute_mask_perms(entries, filter).is_some() {
+        result.push(AclEntry::allow_mask(Perm::empty(), None));
+    }
+
+    let has_mask = result
+        .iter()
+        .any(|entry| entry.kind == AclEntryKind::Mask && !entry.flags.contains(Flag::DEFAULT));
+
+    for entry in &mut result {
+        if entry.flags.contains(Flag::DEFAULT) {
+            continue;
+        }
+
+        let shift = match entry.kind {
+            AclEntryKind::User if entry.name.is_empty() => 6,
+            AclEntryKind::Group if entry.name.is_empty() && !has_mask => 3,
+            AclEntryKind::Mask => 3,
+            AclEntryKind::Other => 0,
+            _ => continue,
+        };
+        entry.perms = Perm::from_bits_truncate((mode >> shift) & 7);
+    }
+
+    result
+}
+
+/// Return the traditional `mode` permission bits for an ACL.
+///
+/// This is the reverse of [`apply_mode`]. The group bits come from the mask
+/// entry, if there is one, or else from the owning group entry. Entries for
+/// the default ACL are ignored.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+#[must_use]
+pub fn to_mode(entries: &[AclEntry]) -> u32 {
+    let rwx = Perm::READ | Perm::WRITE | Perm::EXECUTE;
+    let mut owner = 0;
+    let mut group = 0;
+    let mut mask = None;
+    let mut other = 0;
+
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) {
+            continue;
+        }
+
+        #[allow(clippy::unnecessary_cast)]
+        let bits = (entry.perms & rwx).bits() as u32;
+        match entry.kind {
+            AclEntryKind::User if entry.name.is_empty() => owner = bits,
+            AclEntryKind::Group if entry.name.is_empty() => group = bits,
+            AclEntryKind::Mask => mask = Some(bits),
+            AclEntryKind::Other => other = bits,
+            _ => (),
+        }
+    }
+
+    (owner << 6) | (mask.unwrap_or(group) << 3) | other
+}
+
+/// Change the mode of a file or directory, keeping its ACL consistent.
+///
+/// Reads the access ACL, updates it using [`apply_mode`], and writes it back.
+/// When the file has an extended ACL, the group bits of `mode` set the mask
+/// entry, as they would with `chmod`. The setuid, setgid and sticky bits of
+/// `mode` are also applied.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn chmod_with_acl<P: AsRef<Path>>(path: P, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = path.as_ref();
+    let entries = apply_mode(&getfacl(path, AclOption::ACCESS_ACL)?, mode);
+    setfacl(&[path], &entries, AclOption::ACCESS_ACL)?;
+
+    // Writing the ACL only changes the permission bits.
+    let special_bits = mode & 0o7000;
+    let old_mode = std::fs::metadata(path)?.permissions().mode();
+    if old_mode & 0o7000 != special_bits {
+        let new_mode = special_bits | to_mode(&entries);
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(new_mode))?;
+    }
+
+    Ok(())
+}
+
+/// Compute the ACL that a new file or directory inherits from its parent.
+///
+/// `parent` is the parent directory's ACL, as returned by [`getfacl`]. Only
+/// its default entries (those with [`Flag::DEFAULT`]) are used. `mode` is the
+/// mode passed to `open` or `mkdir`, and `umask` is the process umask.
+///
+/// Returns the access ACL the new object will have. When `is_dir` is true,
+/// the result also includes the default ACL, which a new directory inherits
+/// unchanged from its parent.
+///
+/// If the parent has no default ACL, the result is the minimal ACL for `mode`
+/// with `umask` applied. Otherwise, `umask` is ignored; the access ACL is a
+/// copy of the default ACL, with the owner, group class and other permissions
+/// limited by `mode`. The group class is the mask entry, if there is one;
+/// otherwise it is the owning group entry.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if the parent's default ACL is missing a required
+/// entry.
+#[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+#[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+pub fn inherit_acl(
+    parent: &[AclEntry],
+    mode: u32,
+    umask: u32,
+    is_dir: bool,
+) -> io::Result<Vec<AclEntry>> {
+    let mut default = parent
+        .iter()
+        .filter(|entry| entry.flags.contains(Flag::DEFAULT))
+        .cloned()
+        .collect::<Vec<AclEntry>>();
+
+    if default.is_empty() {
+        return Ok(from_mode(mode & !umask));
+    }
+
+    let filter = (Flag::DEFAULT, Flag::DEFAULT);
+    if let Some(kind) = Acl::find_missing_entries(&default, filter) {
+        return fail(Error::MissingRequiredEntry {
+            kind,
+            default: true,
+        });
+    }
+
+    if let Some(mask_perms) = Acl::compute_mask_perms(&default, filter) {
+        default.push(AclEntry::allow_mask(mask_perms, Flag::DEFAULT));
+    }
+
+    let has_mask = default.iter().any(|entry| entry.kind == AclEntryKind::Mask);
+    let mut access = default.clone();
+
+    for entry in &mut access {
+        entry.flags.remove(Flag::DEFAULT);
+
+        // Limit the owner, group class and other permissions by `mode`.
+        let shift = match entry.kind {
+            AclEntryKind::User if entry.name.is_empty() => 6,
+            AclEntryKind::Group if entry.name.is_empty() && !has_mask => 3,
+            AclEntryKind::Mask => 3,
+            AclEntryKind::Other => 0,
+            _ => continue,
+        };
+        entry.perms &= Perm::from_bits_truncate((mode >> shift) & 7);
+    }
+
+    if is_dir {
+        access.append(&mut default);
+    }
+
+    Ok(access)
+}
@@ -0,0 +1,175 @@
+//! Implements canonical ordering of Posix.1e ACL entries.
+
+use crate::acl::Acl;
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::error::Error;
+use crate::failx::fail;
+use crate::flag::Flag;
+use crate::qualifier::Qualifier;
+use crate::resolver::{NssResolver, PrincipalResolver};
+
+use std::io;
+
+/// Sort key of an entry: default ACL flag, rank of tag, and uid/gid.
+type SortKey = (bool, u8, u32);
+
+/// Return the sort key for an entry in canonical order.
+fn sort_key(
+    entry: &AclEntry,
+    index: usize,
+    resolver: &dyn PrincipalResolver,
+) -> io::Result<SortKey> {
+    let is_default = entry.flags.contains(Flag::DEFAULT);
+
+    let (rank, id) = match entry.kind {
+        // Entries with an unknown tag are kept in their original order,
+        // after all other entries.
+        AclEntryKind::Unknown => (6, u32::try_from(index).unwrap_or(u32::MAX)),
+        _ => match entry.qualifier(resolver)? {
+            Qualifier::UserObj => (0, 0),
+            Qualifier::User(uid) => (1, uid),
+            Qualifier::GroupObj => (2, 0),
+            Qualifier::Group(gid) => (3, gid),
+            Qualifier::Mask => (4, 0),
+            Qualifier::Other => (5, 0),
+            #[cfg(target_os = "freebsd")]
+            Qualifier::Everyone => {
+                return fail(Error::NotSupported(
+                    "normalize only supports Posix.1e ACL's".to_string(),
+                ))
+            }
+            Qualifier::Unknown(tag) => {
+                return fail(Error::InvalidEntry(format!("unknown tag: {tag}")))
+            }
+        },
+    };
+
+    Ok((is_default, rank, id))
+}
+
+/// Return entries in canonical Posix.1e order.
+///
+/// Entries are sorted in the order the kernel stores them: owner, named users,
+/// owning group, named groups, mask, then other. Named users and groups are
+/// sorted by uid/gid. The access ACL comes first, followed by the default ACL
+/// (entries with [`Flag::DEFAULT`]).
+///
+/// Entries that resolve to the same principal are merged into one entry,
+/// whose permissions are the union of the duplicates. The first entry's name
+/// is kept.
+///
+/// If an ACL has named users or groups but no mask entry, a mask entry is
+/// added, computed from the group class entries. An existing mask entry is
+/// kept as is, since changing it would change the permissions the ACL grants.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a user or group name can't be resolved, or if
+/// the entries are not a Posix.1e ACL.
+pub fn normalize(entries: &[AclEntry]) -> io::Result<Vec<AclEntry>> {
+    normalize_with(entries, &NssResolver)
+}
+
+/// Return entries in canonical Posix.1e order, using `resolver` to convert
+/// user/group names to uid/gid.
+///
+/// Works like [`normalize`], which uses [`NssResolver`](crate::NssResolver).
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a user or group name can't be resolved, or if
+/// the entries are not a Posix.1e ACL.
+pub fn normalize_with<R>(entries: &[AclEntry], resolver: &R) -> io::Result<Vec<AclEntry>>
+where
+    R: PrincipalResolver + ?Sized,
+{
+    let resolver: &dyn PrincipalResolver = &resolver;
+    let mut keyed = Vec::with_capacity(entries.len() + 2);
+    for (i, entry) in entries.iter().enumerate() {
+        if !entry.allow {
+            return fail(Error::NotSupported(
+                "normalize only supports Posix.1e ACL's".to_string(),
+            ));
+        }
+        keyed.push((sort_key(entry, i, resolver)?, entry.clone()));
+    }
+
+    // Add missing mask entries.
+    for flags in [Flag::empty(), Flag::DEFAULT] {
+        if let Some(perms) = Acl::compute_mask_perms(entries, (flags, Flag::DEFAULT)) {
+            let mask = AclEntry::allow_mask(perms, flags);
+            keyed.push(((!flags.is_empty(), 4, 0), mask));
+        }
+    }
+
+    keyed.sort_by_key(|(key, _)| *key);
+    keyed.dedup_by(|(key, entry), (prev_key, prev)| {
+        if key == prev_key {
+            prev.perms |= entry.perms;
+            true
+        } else {
+            false
+        }
+    });
+
+    Ok(keyed.into_iter().map(|(_, entry)| entry).collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod normalize_tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let entries = crate::from_str(
+            "d:o::
+            o::r
+            g:2000:r
+            u:600:x
+            d:u::rwx
+            g::r
+            u:500:r
+            u::rw
+            u:500:w
+            d:g::rx",
+        )
+        .unwrap();
+
+        let result = normalize(&entries).unwrap();
+        assert_eq!(
+            crate::to_string(&result).unwrap(),
+            "allow::user::read,write
+allow::user:500:read,write
+allow::user:600:execute
+allow::group::read
+allow::group:2000:read
+allow::mask::read,write,execute
+allow::other::read
+allow:default:user::read,write,execute
+allow:default:group::read,execute
+allow:default:other::
+"
+        );
+
+        // Normalizing again doesn't change anything.
+        assert_eq!(normalize(&result).unwrap(), result);
+    }
+
+    #[test]
+    fn test_normalize_keeps_mask() {
+        let entries = crate::from_str("m::r\nu:500:rw\nu::rw\ng::r\no::").unwrap();
+
+        let result = normalize(&entries).unwrap();
+        assert_eq!(
+            crate::to_string(&result).unwrap(),
+            "allow::user::read,write
+allow::user:500:read,write
+allow::group::read
+allow::mask::read
+allow::other::
+"
+        );
+    }
+}
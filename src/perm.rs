@@ -317,13 +317,15 @@ impl std::str::FromStr for Perm {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result = Perm::empty();
 
-        for item in s.split(',') {
-            let word = item.trim();
+        for (offset, word) in format::split_fields(s, ',', usize::MAX) {
             if !word.is_empty() {
                 if let Some(perms) = parse_perm_abbreviation(word) {
                     result |= perms;
                 } else {
-                    result |= word.parse::<PermName>()?.to_perm();
+                    result |= word
+                        .parse::<PermName>()
+                        .map_err(|err| err.at(offset))?
+                        .to_perm();
                 }
             }
         }
@@ -0,0 +1,479 @@
+//! Implements the long text form used by GNU `getfacl` and `setfacl`.
+
+use crate::access::effective_perms;
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::error::Error;
+use crate::failx::fail;
+use crate::flag::Flag;
+use crate::format::{quote_name, unquote_name};
+use crate::perm::Perm;
+
+use bitflags::bitflags;
+use std::io;
+
+bitflags! {
+    /// Controls the output of [`to_posix_text`].
+    #[derive(Default)]
+    pub struct TextOption : u32 {
+        /// Add an `#effective:` comment to entries whose permissions are
+        /// limited by the mask, like GNU `getfacl`.
+        const EFFECTIVE = 0b0001;
+    }
+}
+
+/// Column where `#effective:` comments start, if the entry is short enough.
+const EFFECTIVE_COLUMN: usize = 32;
+
+/// Return the tag name used in the text form.
+fn tag_name(kind: AclEntryKind) -> io::Result<&'static str> {
+    match kind {
+        AclEntryKind::User => Ok("user"),
+        AclEntryKind::Group => Ok("group"),
+        AclEntryKind::Mask => Ok("mask"),
+        AclEntryKind::Other => Ok("other"),
+        #[cfg(target_os = "freebsd")]
+        AclEntryKind::Everyone => fail(Error::NotSupported(
+            "posix text only supports Posix.1e ACL's".to_string(),
+        )),
+        AclEntryKind::Unknown => fail(Error::InvalidEntry(
+            "unsupported kind: \"unknown\"".to_string(),
+        )),
+    }
+}
+
+/// Return permissions in `rwx` form, with `-` for a missing permission.
+fn perm_text(perms: Perm) -> String {
+    [(Perm::READ, 'r'), (Perm::WRITE, 'w'), (Perm::EXECUTE, 'x')]
+        .iter()
+        .map(|(perm, ch)| if perms.contains(*perm) { *ch } else { '-' })
+        .collect()
+}
+
+/// Append an entry to `result` in text form. If `abbreviate` is true, use
+/// the abbreviated tags of the short text form.
+fn write_entry(result: &mut String, entry: &AclEntry, abbreviate: bool) -> io::Result<()> {
+    if !entry.allow {
+        return fail(Error::NotSupported(
+            "allow=false is not supported in posix text".to_string(),
+        ));
+    }
+    if !(entry.flags - Flag::DEFAULT).is_empty() {
+        return fail(Error::InvalidEntry(format!(
+            "unsupported flags: {}",
+            entry.flags
+        )));
+    }
+    if !(entry.perms - (Perm::READ | Perm::WRITE | Perm::EXECUTE)).is_empty() {
+        return fail(Error::InvalidEntry(format!(
+            "unsupported permissions: {}",
+            entry.perms
+        )));
+    }
+
+    let tag = tag_name(entry.kind)?;
+    if entry.flags.contains(Flag::DEFAULT) {
+        result.push_str(if abbreviate { "d:" } else { "default:" });
+    }
+    result.push_str(if abbreviate { &tag[..1] } else { tag });
+    result.push(':');
+    result.push_str(&quote_name(&entry.name));
+    result.push(':');
+    result.push_str(&perm_text(entry.perms));
+
+    Ok(())
+}
+
+/// Write ACL entries in the long text form used by GNU `getfacl`.
+///
+/// Each entry is written on its own line, in the order given:
+///
+/// ```text
+/// user::rw-
+/// user:bob:r-x
+/// group::r--
+/// mask::r-x
+/// other::r--
+/// default:user::rwx
+/// ```
+///
+/// Use [`TextOption::EFFECTIVE`] to add an `#effective:` comment to entries
+/// whose permissions are limited by the mask.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry can't be represented in this format,
+/// e.g. it denies access or has flags other than [`Flag::DEFAULT`].
+pub fn to_posix_text<O>(entries: &[AclEntry], options: O) -> io::Result<String>
+where
+    O: Into<Option<TextOption>>,
+{
+    let options = options.into().unwrap_or_default();
+    let mut result = String::with_capacity(16 * entries.len());
+
+    for (entry, effective) in effective_perms(entries) {
+        let start = result.len();
+        write_entry(&mut result, entry, false)?;
+
+        if options.contains(TextOption::EFFECTIVE) && effective != entry.perms {
+            let len = result.len() - start;
+            let tabs = EFFECTIVE_COLUMN.saturating_sub(len).div_ceil(8).max(1);
+            result.push_str(&"\t".repeat(tabs));
+            result.push_str("#effective:");
+            result.push_str(&perm_text(effective));
+        }
+
+        result.push('\n');
+    }
+
+    Ok(result)
+}
+
+/// Write ACL entries in the short text form, like `acl_to_short_text`.
+///
+/// Entries are abbreviated and separated by commas, in the order given:
+///
+/// ```text
+/// u::rw-,u:bob:r--,g::r--,m::r--,o::---,d:u::rwx
+/// ```
+///
+/// Use [`from_posix_text`] to read the short text form.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if an entry can't be represented in this format,
+/// e.g. it denies access or has flags other than [`Flag::DEFAULT`].
+pub fn to_short_text(entries: &[AclEntry]) -> io::Result<String> {
+    let mut result = String::with_capacity(10 * entries.len());
+
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            result.push(',');
+        }
+        write_entry(&mut result, entry, true)?;
+    }
+
+    Ok(result)
+}
+
+/// Return a parse error for the given line and column.
+fn parse_err<T>(line: usize, column: usize, message: String) -> io::Result<T> {
+    fail(Error::Parse {
+        line,
+        column,
+        message,
+    })
+}
+
+/// Parse permissions in `rwx` form. Order doesn't matter and `-` is ignored.
+fn parse_perms(s: &str) -> Option<Perm> {
+    let mut perms = Perm::empty();
+    for ch in s.chars() {
+        let perm = match ch {
+            'r' => Perm::READ,
+            'w' => Perm::WRITE,
+            'x' => Perm::EXECUTE,
+            '-' => continue,
+            _ => return None,
+        };
+        if perms.contains(perm) {
+            return None;
+        }
+        perms |= perm;
+    }
+
+    Some(perms)
+}
+
+/// Parse one entry of the long text form, starting at `column` of `line`.
+fn parse_entry(text: &str, line: usize, column: usize) -> io::Result<AclEntry> {
+    // Split into fields, keeping track of the column where each starts.
+    let mut fields = Vec::with_capacity(4);
+    let mut col = column;
+    for field in text.split(':') {
+        let indent = field.chars().take_while(|c| c.is_whitespace()).count();
+        fields.push((field.trim(), col + indent));
+        col += field.chars().count() + 1;
+    }
+    let mut fields = fields.into_iter().peekable();
+
+    let mut flags = Flag::empty();
+    if let Some(&("default" | "d", _)) = fields.peek() {
+        flags = Flag::DEFAULT;
+        fields.next();
+    }
+
+    let Some((tag, tag_col)) = fields.next() else {
+        return parse_err(line, column, "missing tag".to_string());
+    };
+    let kind = match tag {
+        "user" | "u" => AclEntryKind::User,
+        "group" | "g" => AclEntryKind::Group,
+        "mask" | "m" => AclEntryKind::Mask,
+        "other" | "o" => AclEntryKind::Other,
+        _ => return parse_err(line, tag_col, format!("unknown tag: {tag:?}")),
+    };
+
+    let rest = fields.collect::<Vec<_>>();
+    let ((name, name_col), (perms, perms_col)) = match (kind, rest.as_slice()) {
+        (_, [name, perms]) => (*name, *perms),
+        // Mask and other entries may leave out the empty qualifier.
+        (AclEntryKind::Mask | AclEntryKind::Other, [perms]) => (("", perms.1), *perms),
+        (_, [] | [_]) => return parse_err(line, col - 1, "missing permissions".to_string()),
+        (_, [_, _, (_, extra_col), ..]) => {
+            return parse_err(line, *extra_col, "unexpected field".to_string())
+        }
+    };
+
+    if !name.is_empty() && matches!(kind, AclEntryKind::Mask | AclEntryKind::Other) {
+        return parse_err(line, rest[0].1, format!("unexpected qualifier: {name:?}"));
+    }
+
+    let Some(name) = unquote_name(name) else {
+        return parse_err(line, name_col, format!("invalid escape sequence: {name:?}"));
+    };
+
+    let Some(perms) = parse_perms(perms) else {
+        return parse_err(line, perms_col, format!("invalid permissions: {perms:?}"));
+    };
+
+    Ok(AclEntry {
+        kind,
+        name,
+        id: None,
+        perms,
+        flags,
+        allow: true,
+    })
+}
+
+/// Read ACL entries from the long or short text form used by GNU `getfacl`.
+///
+/// Each entry is on its own line, as written by [`to_posix_text`], or entries
+/// are separated by commas, as written by [`to_short_text`]. Tags may be
+/// abbreviated to `u`, `g`, `m` and `o`, and `default:` to `d:`. Permissions
+/// are `r`, `w` and `x` in any order, with `-` ignored. The empty qualifier of
+/// `mask` and `other` entries may be left out. A comment begins with `#` and
+/// proceeds to the end of the line, so `#effective:` comments are ignored.
+///
+/// ```text
+/// user::rw-
+/// u:bob:r-x       #effective:r--
+/// g::r,m::r
+/// o:r
+/// d:u::rwx
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure. If an entry can't be parsed, the error
+/// wraps an [`Error::Parse`] with the line and column.
+pub fn from_posix_text(s: &str) -> io::Result<Vec<AclEntry>> {
+    let mut result = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        result.extend(parse_line(line, i + 1)?);
+    }
+
+    Ok(result)
+}
+
+/// Parse one line of the text form. The line may have several entries,
+/// separated by commas. Empty entries and comments are skipped.
+pub(crate) fn parse_line(line: &str, lineno: usize) -> io::Result<Vec<AclEntry>> {
+    let text = line.find('#').map_or(line, |n| &line[..n]);
+    let mut result = Vec::new();
+
+    let mut column = 1;
+    for item in text.split(',') {
+        let trimmed = item.trim();
+        if !trimmed.is_empty() {
+            let indent = item.chars().take_while(|c| c.is_whitespace()).count();
+            result.push(parse_entry(trimmed, lineno, column + indent)?);
+        }
+        column += item.chars().count() + 1;
+    }
+
+    Ok(result)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod posix_text_tests {
+    use super::*;
+
+    #[test]
+    fn test_to_posix_text() {
+        let entries = crate::from_str(
+            "u::rw
+            u:500:rwx
+            g::r
+            g:bin:rw
+            m::r
+            o::
+            d:u::rwx
+            d:g::rx
+            d:o::r",
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_posix_text(&entries, None).unwrap(),
+            "user::rw-
+user:500:rwx
+group::r--
+group:bin:rw-
+mask::r--
+other::---
+default:user::rwx
+default:group::r-x
+default:other::r--
+"
+        );
+
+        assert_eq!(
+            to_posix_text(&entries[..5], TextOption::EFFECTIVE).unwrap(),
+            "user::rw-
+user:500:rwx\t\t\t#effective:r--
+group::r--
+group:bin:rw-\t\t\t#effective:r--
+mask::r--
+"
+        );
+    }
+
+    #[test]
+    fn test_to_posix_text_unsupported() {
+        let mut entries = crate::from_mode(0o644);
+        entries[0].allow = false;
+        assert!(to_posix_text(&entries, None).is_err());
+
+        let mut entries = vec![AclEntry::allow_user("", Perm::READ, None)];
+        entries[0].kind = AclEntryKind::Unknown;
+        let err = to_posix_text(&entries, None).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported kind: \"unknown\"");
+    }
+
+    #[test]
+    fn test_from_posix_text() {
+        let input = "# file: foo
+user::rw-
+user:500:r-x\t\t\t#effective:r--
+  g::r
+group:bin:wr
+m:r
+o::
+d:u::rwx
+default:mask::---
+";
+        let entries = from_posix_text(input).unwrap();
+        assert_eq!(
+            crate::to_string(&entries).unwrap(),
+            "allow::user::read,write
+allow::user:500:read,execute
+allow::group::read
+allow::group:bin:read,write
+allow::mask::read
+allow::other::
+allow:default:user::read,write,execute
+allow:default:mask::
+"
+        );
+
+        // Round trip.
+        let text = to_posix_text(&entries, TextOption::EFFECTIVE).unwrap();
+        assert_eq!(from_posix_text(&text).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_from_posix_text_errors() {
+        let err_at = |s: &str| {
+            let err = from_posix_text(s).unwrap_err();
+            match Error::from_io(&err) {
+                Some(Error::Parse { line, column, .. }) => (*line, *column, err.to_string()),
+                _ => panic!("unexpected error: {err}"),
+            }
+        };
+
+        assert_eq!(
+            err_at("user::rw-\n  foo::rw-"),
+            (2, 3, "line 2, column 3: unknown tag: \"foo\"".to_string())
+        );
+        assert_eq!(
+            err_at("user::rwz"),
+            (
+                1,
+                7,
+                "line 1, column 7: invalid permissions: \"rwz\"".to_string()
+            )
+        );
+        assert_eq!(
+            err_at("user:bob"),
+            (1, 9, "line 1, column 9: missing permissions".to_string())
+        );
+        assert_eq!(
+            err_at("user:bob:rw:x"),
+            (1, 13, "line 1, column 13: unexpected field".to_string())
+        );
+        assert_eq!(
+            err_at("mask:bob:rw"),
+            (
+                1,
+                6,
+                "line 1, column 6: unexpected qualifier: \"bob\"".to_string()
+            )
+        );
+        assert_eq!(
+            err_at("d:"),
+            (1, 3, "line 1, column 3: unknown tag: \"\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_escaped_names() {
+        let entries = vec![
+            AclEntry::allow_user("john smith", Perm::READ, None),
+            AclEntry::allow_group("a:b,c#d", Perm::READ, None),
+        ];
+
+        let text = to_posix_text(&entries, None).unwrap();
+        assert_eq!(
+            text,
+            "user:john\\040smith:r--\ngroup:a\\072b\\054c\\043d:r--\n"
+        );
+        assert_eq!(from_posix_text(&text).unwrap(), entries);
+
+        let text = to_short_text(&entries).unwrap();
+        assert_eq!(from_posix_text(&text).unwrap(), entries);
+
+        let err = from_posix_text("user:x\\9:r").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 6: invalid escape sequence: \"x\\\\9\""
+        );
+    }
+
+    #[test]
+    fn test_short_text() {
+        let entries = from_posix_text("u::rw-,u:bob:r--, g::r--,m::r--,o::---,d:u::rwx").unwrap();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[1], AclEntry::allow_user("bob", Perm::READ, None));
+        assert_eq!(
+            entries[5],
+            AclEntry::allow_user("", Perm::all(), Flag::DEFAULT)
+        );
+
+        assert_eq!(
+            to_short_text(&entries).unwrap(),
+            "u::rw-,u:bob:r--,g::r--,m::r--,o::---,d:u::rwx"
+        );
+        assert_eq!(to_short_text(&[]).unwrap(), "");
+
+        // Columns are counted from the start of the line.
+        let err = from_posix_text("u::rw-,  g::rwz").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 13: invalid permissions: \"rwz\""
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Provides `Principal` implementation.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::flag::Flag;
+use crate::format;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::qualifier::Qualifier;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::resolver::PrincipalResolver;
+
+use std::fmt;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use std::io;
+
+/// Identifies the ACL entry for a principal, without the permissions.
+///
+/// Used to select entries to remove from an ACL. Two principals match the
+/// same entry if they have the same kind, the same user or group after name
+/// resolution, and both or neither have the [`Flag::DEFAULT`] flag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    /// Kind of entry (User, Group, Other, Mask, Everyone, or Unknown).
+    pub kind: AclEntryKind,
+
+    /// Name of the principal. You can use a user/group name or decimal
+    /// uid/gid.
+    pub name: String,
+
+    /// Flags of the entry. Only [`Flag::DEFAULT`] is significant.
+    pub flags: Flag,
+}
+
+impl Principal {
+    /// Construct a new principal.
+    fn new(kind: AclEntryKind, name: &str, flags: Option<Flag>) -> Principal {
+        Principal {
+            kind,
+            name: String::from(name),
+            flags: flags.unwrap_or_default(),
+        }
+    }
+
+    /// Construct a principal for a user.
+    #[must_use]
+    pub fn user<F>(name: &str, flags: F) -> Principal
+    where
+        F: Into<Option<Flag>>,
+    {
+        Principal::new(AclEntryKind::User, name, flags.into())
+    }
+
+    /// Construct a principal for a group.
+    #[must_use]
+    pub fn group<F>(name: &str, flags: F) -> Principal
+    where
+        F: Into<Option<Flag>>,
+    {
+        Principal::new(AclEntryKind::Group, name, flags.into())
+    }
+
+    /// Construct a principal for the mask.
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    #[must_use]
+    pub fn mask<F>(flags: F) -> Principal
+    where
+        F: Into<Option<Flag>>,
+    {
+        Principal::new(AclEntryKind::Mask, "", flags.into())
+    }
+
+    /// Construct a principal for other.
+    #[cfg(any(docsrs, target_os = "linux", target_os = "freebsd"))]
+    #[cfg_attr(docsrs, doc(cfg(any(target_os = "linux", target_os = "freebsd"))))]
+    #[must_use]
+    pub fn other<F>(flags: F) -> Principal
+    where
+        F: Into<Option<Flag>>,
+    {
+        Principal::new(AclEntryKind::Other, "", flags.into())
+    }
+
+    /// Return the qualifier for this principal, using `resolver` to look up
+    /// the name.
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    pub(crate) fn qualifier(&self, resolver: &dyn PrincipalResolver) -> io::Result<Qualifier> {
+        Qualifier::from_kind(self.kind, &self.name, resolver)
+    }
+}
+
+impl From<&AclEntry> for Principal {
+    fn from(entry: &AclEntry) -> Principal {
+        Principal::new(entry.kind, &entry.name, Some(entry.flags))
+    }
+}
+
+impl fmt::Display for Principal {
+    /// Format a `Principal` 3-tuple:
+    ///   <flags>:<kind>:<name>
+    ///
+    /// Special characters in the name are written as octal escapes, like
+    /// [`AclEntry`].
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.flags,
+            self.kind,
+            format::quote_name(&self.name)
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod principal_tests {
+    use super::*;
+    use crate::perm::Perm;
+
+    #[test]
+    fn test_display() {
+        let principal = Principal::user("DOMAIN\\john smith:x", None);
+        assert_eq!(
+            principal.to_string(),
+            ":user:DOMAIN\\134john\\040smith\\072x"
+        );
+
+        let entry = AclEntry::allow_group("a,b#c", Perm::READ, None);
+        assert_eq!(Principal::from(&entry).to_string(), ":group:a\\054b\\043c");
+        assert!(entry.to_string().contains(":a\\054b\\043c:"));
+    }
+}
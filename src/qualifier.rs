@@ -1,6 +1,9 @@
 //! Implements the `Qualifier` type for internal use
 
+use crate::aclentry::AclEntryKind;
+use crate::error::Error;
 use crate::failx::*;
+use crate::resolver::PrincipalResolver;
 use crate::unix;
 use std::fmt;
 use std::io;
@@ -56,8 +59,8 @@ impl Qualifier {
 
     /// Create qualifier object from a user name.
     #[cfg(target_os = "macos")]
-    pub fn user_named(name: &str) -> io::Result<Qualifier> {
-        match unix::name_to_uid(name) {
+    pub fn user_named(name: &str, resolver: &dyn PrincipalResolver) -> io::Result<Qualifier> {
+        match resolver.name_to_uid(name) {
             Ok(uid) => Ok(Qualifier::User(uid)),
             Err(err) => {
                 // Try to parse name as a GUID.
@@ -68,10 +71,10 @@ impl Qualifier {
 
     /// Create qualifier object from a user name.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    pub fn user_named(name: &str) -> io::Result<Qualifier> {
+    pub fn user_named(name: &str, resolver: &dyn PrincipalResolver) -> io::Result<Qualifier> {
         match name {
             OWNER_NAME => Ok(Qualifier::UserObj),
-            s => match unix::name_to_uid(s) {
+            s => match resolver.name_to_uid(s) {
                 Ok(uid) => Ok(Qualifier::User(uid)),
                 Err(err) => Err(err),
             },
@@ -80,8 +83,8 @@ impl Qualifier {
 
     /// Create qualifier object from a group name.
     #[cfg(target_os = "macos")]
-    pub fn group_named(name: &str) -> io::Result<Qualifier> {
-        match unix::name_to_gid(name) {
+    pub fn group_named(name: &str, resolver: &dyn PrincipalResolver) -> io::Result<Qualifier> {
+        match resolver.name_to_gid(name) {
             Ok(gid) => Ok(Qualifier::Group(gid)),
             Err(err) => Uuid::parse_str(name).map_or(Err(err), Qualifier::from_guid),
         }
@@ -89,10 +92,10 @@ impl Qualifier {
 
     /// Create qualifier object from a group name.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-    pub fn group_named(name: &str) -> io::Result<Qualifier> {
+    pub fn group_named(name: &str, resolver: &dyn PrincipalResolver) -> io::Result<Qualifier> {
         match name {
             OWNER_NAME => Ok(Qualifier::GroupObj),
-            s => match unix::name_to_gid(s) {
+            s => match resolver.name_to_gid(s) {
                 Ok(gid) => Ok(Qualifier::Group(gid)),
                 Err(err) => Err(err),
             },
@@ -104,7 +107,7 @@ impl Qualifier {
     pub fn mask_named(name: &str) -> io::Result<Qualifier> {
         match name {
             MASK_NAME => Ok(Qualifier::Mask),
-            s => fail_custom(&format!("unknown mask name: {s:?}")),
+            s => fail(Error::InvalidEntry(format!("unknown mask name: {s:?}"))),
         }
     }
 
@@ -113,7 +116,7 @@ impl Qualifier {
     pub fn other_named(name: &str) -> io::Result<Qualifier> {
         match name {
             OTHER_NAME => Ok(Qualifier::Other),
-            s => fail_custom(&format!("unknown other name: {s:?}")),
+            s => fail(Error::InvalidEntry(format!("unknown other name: {s:?}"))),
         }
     }
 
@@ -122,7 +125,30 @@ impl Qualifier {
     pub fn everyone_named(name: &str) -> io::Result<Qualifier> {
         match name {
             EVERYONE_NAME => Ok(Qualifier::Everyone),
-            s => fail_custom(&format!("unknown everyone name: {s:?}")),
+            s => fail(Error::InvalidEntry(format!("unknown everyone name: {s:?}"))),
+        }
+    }
+
+    /// Create qualifier object from an entry's kind and name.
+    ///
+    /// User and group names are resolved with `resolver`.
+    pub fn from_kind(
+        kind: AclEntryKind,
+        name: &str,
+        resolver: &dyn PrincipalResolver,
+    ) -> io::Result<Qualifier> {
+        match kind {
+            AclEntryKind::User => Qualifier::user_named(name, resolver),
+            AclEntryKind::Group => Qualifier::group_named(name, resolver),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            AclEntryKind::Mask => Qualifier::mask_named(name),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            AclEntryKind::Other => Qualifier::other_named(name),
+            #[cfg(target_os = "freebsd")]
+            AclEntryKind::Everyone => Qualifier::everyone_named(name),
+            AclEntryKind::Unknown => fail(Error::InvalidEntry(
+                "unsupported kind: \"unknown\"".to_string(),
+            )),
         }
     }
 
@@ -133,15 +159,15 @@ impl Qualifier {
             Qualifier::User(uid) => unix::uid_to_guid(*uid),
             Qualifier::Group(gid) => unix::gid_to_guid(*gid),
             Qualifier::Guid(guid) => Ok(*guid),
-            Qualifier::Unknown(tag) => fail_custom(&format!("unknown tag: {tag:?}")),
+            Qualifier::Unknown(tag) => fail(Error::InvalidEntry(format!("unknown tag: {tag:?}"))),
         }
     }
 
-    /// Return the name of the user/group.
-    pub fn name(&self) -> io::Result<String> {
+    /// Return the name of the user/group, looked up with `resolver`.
+    pub fn name(&self, resolver: &dyn PrincipalResolver) -> io::Result<String> {
         let result = match self {
-            Qualifier::User(uid) => unix::uid_to_name(*uid)?,
-            Qualifier::Group(gid) => unix::gid_to_name(*gid)?,
+            Qualifier::User(uid) => resolver.uid_to_name(*uid)?,
+            Qualifier::Group(gid) => resolver.gid_to_name(*gid)?,
             #[cfg(target_os = "macos")]
             Qualifier::Guid(guid) => guid.to_string(),
             #[cfg(any(target_os = "linux", target_os = "freebsd"))]
@@ -187,6 +213,7 @@ impl fmt::Display for Qualifier {
 #[cfg(test)]
 mod qualifier_tests {
     use super::*;
+    use crate::resolver::NssResolver;
 
     #[test]
     #[cfg(target_os = "macos")]
@@ -207,42 +234,44 @@ mod qualifier_tests {
 
     #[test]
     fn test_user_named() {
-        let user = Qualifier::user_named("89").ok();
+        let user = Qualifier::user_named("89", &NssResolver).ok();
         assert_eq!(user, Some(Qualifier::User(89)));
 
         #[cfg(target_os = "macos")]
         {
-            let user = Qualifier::user_named("_spotlight").ok();
+            let user = Qualifier::user_named("_spotlight", &NssResolver).ok();
             assert_eq!(user, Some(Qualifier::User(89)));
 
-            let user = Qualifier::user_named("ffffeeee-dddd-cccc-bbbb-aaaa00000059").ok();
+            let user =
+                Qualifier::user_named("ffffeeee-dddd-cccc-bbbb-aaaa00000059", &NssResolver).ok();
             assert_eq!(user, Some(Qualifier::User(89)));
         }
 
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         {
-            let user = Qualifier::user_named("daemon").ok();
+            let user = Qualifier::user_named("daemon", &NssResolver).ok();
             assert_eq!(user, Some(Qualifier::User(1)));
         }
     }
 
     #[test]
     fn test_group_named() {
-        let group = Qualifier::group_named("89").ok();
+        let group = Qualifier::group_named("89", &NssResolver).ok();
         assert_eq!(group, Some(Qualifier::Group(89)));
 
         #[cfg(target_os = "macos")]
         {
-            let group = Qualifier::group_named("_spotlight").ok();
+            let group = Qualifier::group_named("_spotlight", &NssResolver).ok();
             assert_eq!(group, Some(Qualifier::Group(89)));
 
-            let group = Qualifier::group_named("abcdefab-cdef-abcd-efab-cdef00000059").ok();
+            let group =
+                Qualifier::group_named("abcdefab-cdef-abcd-efab-cdef00000059", &NssResolver).ok();
             assert_eq!(group, Some(Qualifier::Group(89)));
         }
 
         #[cfg(any(target_os = "linux", target_os = "freebsd"))]
         {
-            let group = Qualifier::group_named("daemon").ok();
+            let group = Qualifier::group_named("daemon", &NssResolver).ok();
             assert_eq!(group, Some(Qualifier::Group(1)));
         }
     }
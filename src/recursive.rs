@@ -0,0 +1,372 @@
+//! Implements recursive `getfacl` and `setfacl` over directory trees.
+
+use crate::aclentry::AclEntry;
+use crate::failx::path_err;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use crate::flag::Flag;
+use crate::resolver::{NssResolver, PrincipalResolver};
+use crate::{getfacl_with, setfacl_with, AclOption};
+
+use bitflags::bitflags;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+bitflags! {
+    /// Controls how directory trees are walked.
+    #[derive(Default)]
+    pub struct RecursiveOption : u32 {
+        /// Follow symbolic links. Otherwise, symbolic links found in the tree
+        /// are skipped. The root path is always followed.
+        const FOLLOW_SYMLINKS = 0b0001;
+
+        /// Don't descend into directories that are on a different file system
+        /// than the root. The mount point itself is still visited, like
+        /// `getfacl -R --one-file-system`.
+        const ONE_FILE_SYSTEM = 0b0010;
+
+        /// Only set the default ACL on directories. Entries with the
+        /// `Flag::DEFAULT` flag are left out when setting the ACL of a
+        /// non-directory (Linux and FreeBSD only).
+        const DEFAULT_DIRS_ONLY = 0b0100;
+
+        /// Use decimal uid/gid instead of user/group names, like
+        /// [`AclOption::NUMERIC_IDS`].
+        const NUMERIC_IDS = 0b1000;
+    }
+}
+
+impl RecursiveOption {
+    /// Return the options to pass to `getfacl` and `setfacl`.
+    const fn acl_options(self) -> AclOption {
+        if self.contains(RecursiveOption::NUMERIC_IDS) {
+            AclOption::NUMERIC_IDS
+        } else {
+            AclOption::empty()
+        }
+    }
+}
+
+/// Walks a directory tree in depth-first order, visiting each directory
+/// before its contents.
+///
+/// Each item is a path and whether it is a directory. Errors are reported
+/// along with the path that caused them.
+struct Walk {
+    options: RecursiveOption,
+
+    /// Paths left to visit, in reverse order.
+    stack: Vec<PathBuf>,
+
+    /// True until the root path is visited.
+    at_root: bool,
+
+    /// Directory to read on the next call to `next`.
+    pending_dir: Option<PathBuf>,
+
+    /// Device of the root path, when using `ONE_FILE_SYSTEM`.
+    root_dev: Option<u64>,
+
+    /// Directories already visited, by (device, inode).
+    visited: HashSet<(u64, u64)>,
+}
+
+impl Walk {
+    fn new(root: &Path, options: RecursiveOption) -> Walk {
+        Walk {
+            options,
+            stack: vec![root.to_path_buf()],
+            at_root: true,
+            pending_dir: None,
+            root_dev: None,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Push the contents of a directory on the stack.
+    fn read_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let mut children = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+
+        // Visit children in sorted order.
+        children.sort_unstable_by(|a, b| b.cmp(a));
+        self.stack.append(&mut children);
+
+        Ok(())
+    }
+
+    /// Return metadata for a path and whether to descend into it, or None if
+    /// the path should be skipped.
+    fn metadata(&mut self, path: &Path) -> io::Result<Option<(fs::Metadata, bool)>> {
+        let is_root = std::mem::replace(&mut self.at_root, false);
+        let meta = if is_root || self.options.contains(RecursiveOption::FOLLOW_SYMLINKS) {
+            fs::metadata(path)?
+        } else {
+            fs::symlink_metadata(path)?
+        };
+
+        if meta.file_type().is_symlink() {
+            return Ok(None);
+        }
+
+        // Don't visit the same directory twice. This avoids cycles when
+        // following symlinks.
+        if meta.is_dir() && !self.visited.insert((meta.dev(), meta.ino())) {
+            return Ok(None);
+        }
+
+        // Visit a mount point, but not its contents.
+        let mut descend = meta.is_dir();
+        if self.options.contains(RecursiveOption::ONE_FILE_SYSTEM) {
+            let root_dev = *self.root_dev.get_or_insert_with(|| meta.dev());
+            descend &= meta.dev() == root_dev;
+        }
+
+        Ok(Some((meta, descend)))
+    }
+}
+
+impl Iterator for Walk {
+    type Item = (PathBuf, io::Result<bool>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(dir) = self.pending_dir.take() {
+            if let Err(err) = self.read_dir(&dir) {
+                return Some((dir, Err(err)));
+            }
+        }
+
+        while let Some(path) = self.stack.pop() {
+            match self.metadata(&path) {
+                Ok(Some((meta, descend))) => {
+                    if descend {
+                        self.pending_dir = Some(path.clone());
+                    }
+                    return Some((path, Ok(meta.is_dir())));
+                }
+                Ok(None) => {}
+                Err(err) => return Some((path, Err(err))),
+            }
+        }
+
+        None
+    }
+}
+
+/// Get access control lists (ACL) for a directory tree.
+///
+/// Returns an iterator that walks the tree at `root`, yielding each path along
+/// with the result of [`getfacl`](crate::getfacl) for that path. A directory
+/// is visited before its contents, and the contents of a directory are
+/// visited in sorted order.
+/// If a directory can't be read, the error is yielded with the directory's
+/// path after the directory itself.
+///
+/// Use [`RecursiveOption`] to control how symbolic links and mount points are
+/// handled.
+///
+/// # Example
+///
+/// ```no_run
+/// use exacl::getfacl_recursive;
+///
+/// for (path, result) in getfacl_recursive("./tmp", None) {
+///     match result {
+///         Ok(entries) => println!("{}: {} entries", path.display(), entries.len()),
+///         Err(err) => eprintln!("{err}"),
+///     }
+/// }
+/// ```
+pub fn getfacl_recursive<P, O>(
+    root: P,
+    options: O,
+) -> impl Iterator<Item = (PathBuf, io::Result<Vec<AclEntry>>)>
+where
+    P: AsRef<Path>,
+    O: Into<Option<RecursiveOption>>,
+{
+    getfacl_recursive_with(root, options, &NssResolver)
+}
+
+/// Get access control lists (ACL) for a directory tree, using `resolver` to
+/// convert uid/gid to user/group names.
+///
+/// Works like [`getfacl_recursive`], which uses
+/// [`NssResolver`](crate::NssResolver). With
+/// [`RecursiveOption::NUMERIC_IDS`], names are not looked up and `resolver`
+/// is not used.
+pub fn getfacl_recursive_with<P, O, R>(
+    root: P,
+    options: O,
+    resolver: &R,
+) -> impl Iterator<Item = (PathBuf, io::Result<Vec<AclEntry>>)> + '_
+where
+    P: AsRef<Path>,
+    O: Into<Option<RecursiveOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let options = options.into().unwrap_or_default();
+
+    let acl_options = options.acl_options();
+
+    Walk::new(root.as_ref(), options).map(move |(path, result)| {
+        let result = match result {
+            Ok(_) => getfacl_with(&path, acl_options, resolver),
+            Err(err) => Err(path_err(&path, &err)),
+        };
+        (path, result)
+    })
+}
+
+/// Set access control list (ACL) for a directory tree.
+///
+/// Walks the tree at `root` and calls [`setfacl`](crate::setfacl) for each
+/// path, including `root` itself. Stops at the first error.
+///
+/// On Linux and `FreeBSD`, setting an ACL with [`Flag::DEFAULT`] entries fails
+/// for a non-directory. Use [`RecursiveOption::DEFAULT_DIRS_ONLY`] to set
+/// only the access ACL on non-directories.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_recursive<P, O>(root: P, entries: &[AclEntry], options: O) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<RecursiveOption>>,
+{
+    setfacl_recursive_with(root, entries, options, &NssResolver)
+}
+
+/// Set access control list (ACL) for a directory tree, using `resolver` to
+/// convert user/group names to uid/gid.
+///
+/// Works like [`setfacl_recursive`], which uses
+/// [`NssResolver`](crate::NssResolver). With
+/// [`RecursiveOption::NUMERIC_IDS`], names must be decimal uid/gid and
+/// `resolver` is not used.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] on failure.
+pub fn setfacl_recursive_with<P, O, R>(
+    root: P,
+    entries: &[AclEntry],
+    options: O,
+    resolver: &R,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    O: Into<Option<RecursiveOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let options = options.into().unwrap_or_default();
+
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    let file_entries = if options.contains(RecursiveOption::DEFAULT_DIRS_ONLY) {
+        entries
+            .iter()
+            .filter(|entry| !entry.flags.contains(Flag::DEFAULT))
+            .cloned()
+            .collect::<Vec<AclEntry>>()
+    } else {
+        entries.to_vec()
+    };
+
+    #[cfg(target_os = "macos")]
+    let file_entries = entries;
+
+    for (path, result) in Walk::new(root.as_ref(), options) {
+        let is_dir = result.map_err(|err| path_err(&path, &err))?;
+        if is_dir {
+            setfacl_with(&[&path], entries, options.acl_options(), resolver)?;
+        } else {
+            setfacl_with(&[&path], &file_entries, options.acl_options(), resolver)?;
+        }
+    }
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod recursive_tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn make_tree() -> io::Result<tempfile::TempDir> {
+        let dir = tempfile::TempDir::new()?;
+        fs::create_dir(dir.path().join("a"))?;
+        fs::write(dir.path().join("a/f1"), "")?;
+        fs::create_dir(dir.path().join("b"))?;
+        fs::write(dir.path().join("f2"), "")?;
+        symlink(dir.path(), dir.path().join("a/loop"))?;
+        symlink(dir.path().join("f2"), dir.path().join("b/link"))?;
+
+        Ok(dir)
+    }
+
+    fn walk(root: &Path, options: RecursiveOption) -> Vec<String> {
+        Walk::new(root, options)
+            .map(|(path, result)| {
+                let suffix = if result.unwrap() { "/" } else { "" };
+                let path = path.strip_prefix(root).unwrap().display().to_string();
+                format!("{path}{suffix}")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_walk() -> io::Result<()> {
+        let dir = make_tree()?;
+
+        assert_eq!(
+            walk(dir.path(), RecursiveOption::empty()),
+            vec!["/", "a/", "a/f1", "b/", "f2"]
+        );
+
+        // The loop back to root is only visited once.
+        assert_eq!(
+            walk(dir.path(), RecursiveOption::FOLLOW_SYMLINKS),
+            vec!["/", "a/", "a/f1", "b/", "b/link", "f2"]
+        );
+
+        assert_eq!(
+            walk(dir.path(), RecursiveOption::ONE_FILE_SYSTEM),
+            vec!["/", "a/", "a/f1", "b/", "f2"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_mount_point() -> io::Result<()> {
+        let dir = make_tree()?;
+
+        // Pretend everything below the root is on another file system. The
+        // mount points are visited, but not their contents.
+        let mut walk = Walk::new(dir.path(), RecursiveOption::ONE_FILE_SYSTEM);
+        assert_eq!(walk.next().unwrap().0, dir.path());
+        walk.root_dev = walk.root_dev.map(|dev| dev + 1);
+
+        let paths = walk
+            .map(|(path, _)| path.strip_prefix(dir.path()).unwrap().display().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["a", "b", "f2"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_missing_root() {
+        let mut walk = Walk::new(Path::new("/non/existant/path"), RecursiveOption::empty());
+        let (path, result) = walk.next().unwrap();
+        assert_eq!(path, Path::new("/non/existant/path"));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(walk.next().is_none());
+    }
+}
@@ -0,0 +1,603 @@
+//! Implements the lookup of user and group names.
+
+use crate::acl::AclOption;
+use crate::error::Error;
+use crate::failx::{fail, path_err};
+use crate::format;
+use crate::unix;
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::hash::Hash;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Converts between user/group names and uid/gid.
+///
+/// [`getfacl`](crate::getfacl) and [`setfacl`](crate::setfacl) use
+/// [`NssResolver`]. To look up names somewhere else, such as a directory
+/// service or a fixed table in tests, implement this trait and pass it to
+/// [`getfacl_with`](crate::getfacl_with) or
+/// [`setfacl_with`](crate::setfacl_with). Every other function that looks up
+/// names also has a `_with` variant, e.g. `getfacl_fd_with`,
+/// `getfacl_recursive_with` and `validate_with`. Wrap a resolver in a
+/// [`CachingResolver`] to avoid repeating lookups when scanning many files.
+///
+/// `name_to_uid` and `name_to_gid` should return an [`Error::UnknownUser`]
+/// or [`Error::UnknownGroup`] if the name is not found. `uid_to_name` and
+/// `gid_to_name` should return the decimal id if the id has no name.
+///
+/// # Example
+///
+/// ```
+/// use exacl::{Error, PrincipalResolver};
+/// use std::io;
+///
+/// struct Fixed;
+///
+/// impl PrincipalResolver for Fixed {
+///     fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+///         match name {
+///             "alice" => Ok(1001),
+///             _ => Err(Error::UnknownUser(name.to_string()).into()),
+///         }
+///     }
+///
+///     fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+///         match uid {
+///             1001 => Ok("alice".to_string()),
+///             _ => Ok(uid.to_string()),
+///         }
+///     }
+///
+///     fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+///         Err(Error::UnknownGroup(name.to_string()).into())
+///     }
+///
+///     fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+///         Ok(gid.to_string())
+///     }
+/// }
+///
+/// assert_eq!(Fixed.uid_to_name(1001).unwrap(), "alice");
+/// ```
+pub trait PrincipalResolver {
+    /// Convert user name to uid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the name can't be resolved.
+    fn name_to_uid(&self, name: &str) -> io::Result<u32>;
+
+    /// Convert uid to user name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    fn uid_to_name(&self, uid: u32) -> io::Result<String>;
+
+    /// Convert group name to gid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the name can't be resolved.
+    fn name_to_gid(&self, name: &str) -> io::Result<u32>;
+
+    /// Convert gid to group name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if the lookup fails.
+    fn gid_to_name(&self, gid: u32) -> io::Result<String>;
+}
+
+impl<R: PrincipalResolver + ?Sized> PrincipalResolver for &R {
+    fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+        (**self).name_to_uid(name)
+    }
+
+    fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+        (**self).uid_to_name(uid)
+    }
+
+    fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+        (**self).name_to_gid(name)
+    }
+
+    fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+        (**self).gid_to_name(gid)
+    }
+}
+
+/// Resolver that uses the system's user and group database.
+///
+/// Names are looked up with `getpwnam_r`, `getgrnam_r`, et al., so they go
+/// through the name service switch (NSS). A name that isn't found but is a
+/// decimal number is used as the id.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NssResolver;
+
+impl PrincipalResolver for NssResolver {
+    fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+        unix::name_to_uid(name)
+    }
+
+    fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+        unix::uid_to_name(uid)
+    }
+
+    fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+        unix::name_to_gid(name)
+    }
+
+    fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+        unix::gid_to_name(gid)
+    }
+}
+
+/// Resolver that remembers the results of another resolver.
+///
+/// Successful lookups are cached for the life of the `CachingResolver`.
+/// Failed lookups are not cached. The cache is shared by all threads using
+/// the resolver.
+///
+/// # Example
+///
+/// ```no_run
+/// use exacl::{getfacl_with, CachingResolver, NssResolver};
+///
+/// let resolver = CachingResolver::new(NssResolver);
+/// for path in ["./tmp/foo", "./tmp/bar"] {
+///     let entries = getfacl_with(path, None, &resolver)?;
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct CachingResolver<R> {
+    inner: R,
+    cache: Mutex<Cache>,
+}
+
+/// Results of previous lookups.
+#[derive(Debug, Default)]
+struct Cache {
+    uids: HashMap<String, u32>,
+    user_names: HashMap<u32, String>,
+    gids: HashMap<String, u32>,
+    group_names: HashMap<u32, String>,
+}
+
+impl<R> CachingResolver<R> {
+    /// Construct a caching resolver that wraps `inner`.
+    pub fn new(inner: R) -> CachingResolver<R> {
+        CachingResolver {
+            inner,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Forget the results of previous lookups.
+    pub fn clear(&self) {
+        *self.lock() = Cache::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Return the cached value for `key`, or call `resolve` and cache the
+    /// result. The lock is not held while calling `resolve`.
+    fn lookup<K, Q, V, F>(
+        &self,
+        table: fn(&mut Cache) -> &mut HashMap<K, V>,
+        key: &Q,
+        resolve: F,
+    ) -> io::Result<V>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: ToOwned<Owned = K> + Eq + Hash + ?Sized,
+        V: Clone,
+        F: FnOnce() -> io::Result<V>,
+    {
+        if let Some(value) = table(&mut self.lock()).get(key) {
+            return Ok(value.clone());
+        }
+
+        let value = resolve()?;
+        table(&mut self.lock()).insert(key.to_owned(), value.clone());
+        Ok(value)
+    }
+}
+
+impl<R: PrincipalResolver> PrincipalResolver for CachingResolver<R> {
+    fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+        self.lookup(|c| &mut c.uids, name, || self.inner.name_to_uid(name))
+    }
+
+    fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+        self.lookup(|c| &mut c.user_names, &uid, || self.inner.uid_to_name(uid))
+    }
+
+    fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+        self.lookup(|c| &mut c.gids, name, || self.inner.name_to_gid(name))
+    }
+
+    fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+        self.lookup(|c| &mut c.group_names, &gid, || self.inner.gid_to_name(gid))
+    }
+}
+
+/// Resolver that reads the `passwd` and `group` files of another system.
+///
+/// Use this to prepare ACL's for a container image or chroot, where names
+/// must resolve to the ids in the image, not on the host. The files are read
+/// once, when the resolver is constructed. NSS is not used.
+///
+/// Like [`NssResolver`], a name that isn't found but is a decimal number is
+/// used as the id. If a name or id appears more than once, the first entry
+/// is used.
+///
+/// # Example
+///
+/// ```no_run
+/// use exacl::{setfacl_with, AclEntry, FileResolver, Perm};
+///
+/// let resolver = FileResolver::new("./rootfs")?;
+/// let mut entries = exacl::from_mode(0o640);
+/// entries.push(AclEntry::allow_user("postgres", Perm::READ, None));
+/// setfacl_with(&["./rootfs/var/lib/data"], &entries, None, &resolver)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FileResolver {
+    uids: HashMap<String, u32>,
+    user_names: HashMap<u32, String>,
+    gids: HashMap<String, u32>,
+    group_names: HashMap<u32, String>,
+}
+
+impl FileResolver {
+    /// Construct a resolver from `etc/passwd` and `etc/group` under `root`.
+    ///
+    /// Symbolic links are followed as if `root` were the root directory, like
+    /// `chroot`: an absolute link target or a `..` component can't leave
+    /// `root`, so a link such as `etc/passwd -> /etc/passwd` in an image
+    /// doesn't read the host's file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a file can't be read or has a malformed
+    /// line.
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<FileResolver> {
+        let root = root.as_ref();
+        FileResolver::from_files(
+            resolve_in_root(root, Path::new("etc/passwd"))?,
+            resolve_in_root(root, Path::new("etc/group"))?,
+        )
+    }
+
+    /// Construct a resolver from the given `passwd` and `group` files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a file can't be read or has a malformed
+    /// line.
+    pub fn from_files<P, G>(passwd: P, group: G) -> io::Result<FileResolver>
+    where
+        P: AsRef<Path>,
+        G: AsRef<Path>,
+    {
+        let mut resolver = FileResolver::default();
+        read_id_file(
+            passwd.as_ref(),
+            &mut resolver.uids,
+            &mut resolver.user_names,
+        )?;
+        read_id_file(
+            group.as_ref(),
+            &mut resolver.gids,
+            &mut resolver.group_names,
+        )?;
+        Ok(resolver)
+    }
+}
+
+/// Maximum number of symbolic links followed by [`resolve_in_root`], as on
+/// Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Return the path of `path` under `root`, following symbolic links as if
+/// `root` were the root directory.
+fn resolve_in_root(root: &Path, path: &Path) -> io::Result<PathBuf> {
+    // Components left to resolve, in reverse order. "/" stands for the root
+    // directory, since it can't be part of a file name.
+    let mut pending: Vec<OsString> = path
+        .components()
+        .rev()
+        .map(|c| c.as_os_str().to_os_string())
+        .collect();
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+
+    while let Some(name) = pending.pop() {
+        match name.as_bytes() {
+            b"/" => resolved.clear(),
+            b"." => (),
+            b".." => {
+                resolved.pop();
+            }
+            _ => {
+                let full = root.join(&resolved).join(&name);
+                let is_symlink =
+                    fs::symlink_metadata(&full).is_ok_and(|meta| meta.file_type().is_symlink());
+                if !is_symlink {
+                    // A missing file is reported when the file is read.
+                    resolved.push(name);
+                    continue;
+                }
+
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    let err = Error::InvalidInput("too many levels of symbolic links".to_string());
+                    return Err(path_err(&root.join(path), &err.into()));
+                }
+                let target = fs::read_link(&full).map_err(|err| path_err(&full, &err))?;
+                pending.extend(
+                    target
+                        .components()
+                        .rev()
+                        .map(|c| c.as_os_str().to_os_string()),
+                );
+            }
+        }
+    }
+
+    Ok(root.join(resolved))
+}
+
+/// Read the names and ids from a `passwd` or `group` file. In both formats,
+/// the name is the first field and the id is the third.
+fn read_id_file(
+    path: &Path,
+    names: &mut HashMap<String, u32>,
+    ids: &mut HashMap<u32, String>,
+) -> io::Result<()> {
+    let text = fs::read_to_string(path).map_err(|err| path_err(path, &err))?;
+
+    for (i, line) in text.lines().enumerate() {
+        // Skip comments and NIS compat entries ("+" or "-").
+        if line.trim().is_empty() || line.starts_with(['#', '+', '-']) {
+            continue;
+        }
+
+        let fields = format::split_fields(line, ':', 4);
+        let parse_err = |column: usize, message: String| {
+            let err = Error::Parse {
+                line: i + 1,
+                column: column + 1,
+                message,
+            };
+            path_err(path, &err.into())
+        };
+
+        let Some(&(column, id)) = fields.get(2) else {
+            return Err(parse_err(line.chars().count(), "missing id".to_string()));
+        };
+        let id = id
+            .parse::<u32>()
+            .map_err(|_| parse_err(column, format!("invalid id: {id:?}")))?;
+
+        let name = fields[0].1;
+        names.entry(name.to_string()).or_insert(id);
+        ids.entry(id).or_insert_with(|| name.to_string());
+    }
+
+    Ok(())
+}
+
+impl PrincipalResolver for FileResolver {
+    fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+        if let Some(uid) = self.uids.get(name) {
+            return Ok(*uid);
+        }
+        name.parse::<u32>()
+            .or_else(|_| fail(Error::UnknownUser(name.to_string())))
+    }
+
+    fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+        Ok(self
+            .user_names
+            .get(&uid)
+            .cloned()
+            .unwrap_or_else(|| uid.to_string()))
+    }
+
+    fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+        if let Some(gid) = self.gids.get(name) {
+            return Ok(*gid);
+        }
+        name.parse::<u32>()
+            .or_else(|_| fail(Error::UnknownGroup(name.to_string())))
+    }
+
+    fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+        Ok(self
+            .group_names
+            .get(&gid)
+            .cloned()
+            .unwrap_or_else(|| gid.to_string()))
+    }
+}
+
+/// Resolver used for [`AclOption::NUMERIC_IDS`]. Names must be decimal ids,
+/// and ids are written in decimal without being looked up.
+pub(crate) struct NumericResolver;
+
+impl PrincipalResolver for NumericResolver {
+    fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+        name.parse::<u32>()
+            .or_else(|_| fail(Error::InvalidEntry(format!("invalid user id: {name:?}"))))
+    }
+
+    fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+        Ok(uid.to_string())
+    }
+
+    fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+        name.parse::<u32>()
+            .or_else(|_| fail(Error::InvalidEntry(format!("invalid group id: {name:?}"))))
+    }
+
+    fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+        Ok(gid.to_string())
+    }
+}
+
+/// Return the resolver to use for `options`. With [`AclOption::NUMERIC_IDS`],
+/// names are not looked up and `resolver` is not used.
+pub(crate) fn for_options(
+    options: AclOption,
+    resolver: &dyn PrincipalResolver,
+) -> &dyn PrincipalResolver {
+    if options.contains(AclOption::NUMERIC_IDS) {
+        &NumericResolver
+    } else {
+        resolver
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod resolver_tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Resolver that counts lookups.
+    #[derive(Default)]
+    struct Counting {
+        calls: Cell<usize>,
+    }
+
+    impl PrincipalResolver for Counting {
+        fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+            self.calls.set(self.calls.get() + 1);
+            match name {
+                "alice" => Ok(1001),
+                _ => fail(Error::UnknownUser(name.to_string())),
+            }
+        }
+
+        fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(format!("user{uid}"))
+        }
+
+        fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+            self.calls.set(self.calls.get() + 1);
+            fail(Error::UnknownGroup(name.to_string()))
+        }
+
+        fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(format!("group{gid}"))
+        }
+    }
+
+    #[test]
+    fn test_caching_resolver() {
+        let resolver = CachingResolver::new(Counting::default());
+
+        assert_eq!(resolver.name_to_uid("alice").unwrap(), 1001);
+        assert_eq!(resolver.name_to_uid("alice").unwrap(), 1001);
+        assert_eq!(resolver.uid_to_name(5).unwrap(), "user5");
+        assert_eq!(resolver.uid_to_name(5).unwrap(), "user5");
+        assert_eq!(resolver.gid_to_name(5).unwrap(), "group5");
+        assert_eq!(resolver.inner.calls.get(), 3);
+
+        // Failures are not cached.
+        assert!(resolver.name_to_gid("staff").is_err());
+        assert!(resolver.name_to_gid("staff").is_err());
+        assert_eq!(resolver.inner.calls.get(), 5);
+
+        resolver.clear();
+        assert_eq!(resolver.name_to_uid("alice").unwrap(), 1001);
+        assert_eq!(resolver.inner.calls.get(), 6);
+    }
+
+    #[test]
+    fn test_file_resolver() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("etc")).unwrap();
+        fs::write(
+            dir.path().join("etc/passwd"),
+            "# comment\nroot:x:0:0:root:/root:/bin/sh\npostgres:x:70:70::/var/lib/postgresql:/bin/sh\n+\npg:x:70:70:::\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("etc/group"), "root:x:0:\npostgres:x:70:\n").unwrap();
+
+        let resolver = FileResolver::new(dir.path()).unwrap();
+        assert_eq!(resolver.name_to_uid("postgres").unwrap(), 70);
+        assert_eq!(resolver.name_to_uid("pg").unwrap(), 70);
+        assert_eq!(resolver.uid_to_name(70).unwrap(), "postgres");
+        assert_eq!(resolver.uid_to_name(1000).unwrap(), "1000");
+        assert_eq!(resolver.name_to_gid("postgres").unwrap(), 70);
+        assert_eq!(resolver.name_to_uid("1000").unwrap(), 1000);
+
+        let err = resolver.name_to_gid("staff").unwrap_err();
+        assert_eq!(err.to_string(), "unknown group name: \"staff\"");
+
+        fs::write(dir.path().join("etc/group"), "root:x:0:\nbad:x:abc:\n").unwrap();
+        let err = FileResolver::new(dir.path()).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("line 2, column 7: invalid id: \"abc\""),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_file_resolver_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("etc")).unwrap();
+        fs::create_dir_all(dir.path().join("data")).unwrap();
+        fs::write(dir.path().join("data/passwd"), "alice:x:1000:1000:::\n").unwrap();
+        fs::write(dir.path().join("group.real"), "staff:x:50:\n").unwrap();
+
+        // Absolute and relative targets are resolved under the root, not the
+        // host's root directory.
+        symlink("/data/passwd", dir.path().join("etc/passwd")).unwrap();
+        symlink("../../../../../group.real", dir.path().join("etc/group")).unwrap();
+
+        let resolver = FileResolver::new(dir.path()).unwrap();
+        assert_eq!(resolver.name_to_uid("alice").unwrap(), 1000);
+        assert!(resolver.name_to_uid("root").is_err());
+        assert_eq!(resolver.name_to_gid("staff").unwrap(), 50);
+        assert!(resolver.name_to_gid("root").is_err());
+
+        // A link to itself is a loop.
+        fs::remove_file(dir.path().join("etc/passwd")).unwrap();
+        symlink("/etc/passwd", dir.path().join("etc/passwd")).unwrap();
+        let err = FileResolver::new(dir.path()).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("too many levels of symbolic links"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_numeric_resolver() {
+        assert_eq!(NumericResolver.name_to_uid("500").unwrap(), 500);
+        assert_eq!(NumericResolver.gid_to_name(20).unwrap(), "20");
+
+        let err = NumericResolver.name_to_gid("staff").unwrap_err();
+        assert_eq!(err.to_string(), "invalid group id: \"staff\"");
+    }
+}
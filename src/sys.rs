@@ -130,6 +130,10 @@ pub mod sg {
     pub const EINVAL: i32 = super::EINVAL as i32;
     pub const ENOMEM: i32 = super::ENOMEM as i32;
     pub const ERANGE: i32 = super::ERANGE as i32;
+    #[cfg(target_os = "linux")]
+    pub const EACCES: i32 = super::EACCES as i32;
+    #[cfg(target_os = "linux")]
+    pub const ENODATA: i32 = super::ENODATA as i32;
     pub const ACL_MAX_ENTRIES: i32 = super::ACL_MAX_ENTRIES as i32;
 
     #[cfg(target_os = "macos")]
@@ -199,6 +203,10 @@ pub mod sg {
         assert!(super::ENOTSUP as i32 >= 0);
         assert!(super::EINVAL as i32 >= 0);
         assert!(super::ENOMEM as i32 >= 0);
+        #[cfg(target_os = "linux")]
+        assert!(super::EACCES as i32 >= 0);
+        #[cfg(target_os = "linux")]
+        assert!(super::ENODATA as i32 >= 0);
         assert!(super::ACL_MAX_ENTRIES as i32 >= 0);
 
         #[cfg(target_os = "linux")]
@@ -1,5 +1,6 @@
 //! Implements utilities for converting user/group names to uid/gid.
 
+use crate::error::Error;
 use crate::failx::*;
 use crate::sys::{getgrgid_r, getgrnam_r, getpwnam_r, getpwuid_r, group, passwd, sg};
 #[cfg(target_os = "macos")]
@@ -68,7 +69,7 @@ pub fn name_to_uid(name: &str) -> io::Result<uid_t> {
         return Ok(num);
     }
 
-    fail_custom(&format!("unknown user name: {name:?}"))
+    fail(Error::UnknownUser(name.to_string()))
 }
 
 /// Convert group name to gid.
@@ -112,7 +113,7 @@ pub fn name_to_gid(name: &str) -> io::Result<gid_t> {
         return Ok(num);
     }
 
-    fail_custom(&format!("unknown group name: {name:?}"))
+    fail(Error::UnknownGroup(name.to_string()))
 }
 
 /// Convert uid to user name.
@@ -244,9 +245,9 @@ pub fn guid_to_id(guid: Uuid) -> io::Result<(Option<uid_t>, Option<gid_t>)> {
         sg::ID_TYPE_UID => (Some(id_c), None),
         sg::ID_TYPE_GID => (None, Some(id_c)),
         _ => {
-            return fail_custom(&format!(
+            return fail(Error::InvalidEntry(format!(
                 "mbr_uuid_to_id: Unknown idtype {idtype:?} for guid {guid:?}"
-            ))
+            )))
         }
     };
 
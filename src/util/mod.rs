@@ -14,36 +14,52 @@
 //!    `xacl_get_entry` - retrieve contents from an ACL entry
 //!    `xacl_get_file`  - get ACL from file path
 //!    `xacl_set_file`  - set ACL for file path
+//!    `xacl_get_fd`    - get ACL from open file descriptor
+//!    `xacl_set_fd`    - set ACL for open file descriptor
 //!    `xacl_is_nfs4`   - return true if file path uses `NFSv4` ACL on `FreeBSD`
+//!
+//! On Linux, the `xattr_backend` feature replaces libacl with an
+//! implementation that reads and writes the ACL extended attributes directly.
 
+#[cfg(not(all(target_os = "linux", feature = "xattr_backend")))]
 mod util_common;
 
 #[cfg(target_os = "freebsd")]
 mod util_freebsd;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "xattr_backend")))]
 mod util_linux;
 
 #[cfg(target_os = "macos")]
 mod util_macos;
 
+#[cfg(all(target_os = "linux", feature = "xattr_backend"))]
+mod util_xattr;
+
 // Re-export acl_entry_t and acl_t from crate::sys.
+#[cfg(not(all(target_os = "linux", feature = "xattr_backend")))]
 pub use crate::sys::{acl_entry_t, acl_t};
 
 #[cfg(target_os = "freebsd")]
 pub use util_freebsd::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_nfs4, xacl_is_posix, xacl_set_file,
+    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_fd, xacl_get_file, xacl_init,
+    xacl_is_empty, xacl_is_nfs4, xacl_is_posix, xacl_set_fd, xacl_set_file,
 };
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "xattr_backend")))]
 pub use util_linux::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_posix, xacl_set_file,
+    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_fd, xacl_get_file, xacl_init,
+    xacl_is_empty, xacl_is_posix, xacl_set_fd, xacl_set_file,
+};
+
+#[cfg(all(target_os = "linux", feature = "xattr_backend"))]
+pub use util_xattr::{
+    acl_entry_t, acl_t, xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_fd,
+    xacl_get_file, xacl_init, xacl_is_empty, xacl_is_posix, xacl_set_fd, xacl_set_file,
 };
 
 #[cfg(target_os = "macos")]
 pub use util_macos::{
-    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_file, xacl_init,
-    xacl_is_empty, xacl_is_posix, xacl_set_file,
+    xacl_add_entry, xacl_foreach, xacl_free, xacl_get_entry, xacl_get_fd, xacl_get_file, xacl_init,
+    xacl_is_empty, xacl_is_posix, xacl_set_fd, xacl_set_file,
 };
@@ -1,4 +1,5 @@
 use crate::bititer::BitIter;
+use crate::error::Error;
 use crate::failx::*;
 use crate::perm::Perm;
 use crate::sys::*;
@@ -61,7 +62,7 @@ pub fn xacl_foreach<F: FnMut(acl_entry_t) -> io::Result<()>>(
 pub fn xacl_init(capacity: usize) -> io::Result<acl_t> {
     let size = match i32::try_from(capacity) {
         Ok(size) if size <= sg::ACL_MAX_ENTRIES => size,
-        _ => return fail_custom("Too many ACL entries"),
+        _ => return fail(Error::InvalidInput("Too many ACL entries".to_string())),
     };
 
     let acl = unsafe { acl_init(size) };
@@ -1,4 +1,5 @@
 use crate::bititer::BitIter;
+use crate::error::Error;
 use crate::failx::*;
 use crate::flag::Flag;
 use crate::perm::Perm;
@@ -11,6 +12,7 @@ use scopeguard::defer;
 use std::ffi::{c_void, CString};
 use std::io;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 use std::ptr;
 
@@ -85,7 +87,7 @@ pub fn xacl_get_file(path: &Path, symlink_acl: bool, default_acl: bool) -> io::R
     {
         // NFSv4 does not support default ACL.
         if default_acl {
-            return fail_custom("Default ACL not supported");
+            return fail(Error::NotSupported("Default ACL not supported".to_string()));
         }
 
         acl_type = sg::ACL_TYPE_NFS4;
@@ -166,7 +168,7 @@ pub fn xacl_set_file(
     let is_nfs4 = xacl_is_nfs4(path, symlink_acl)?;
 
     if default_acl && is_nfs4 {
-        return fail_custom("Default ACL not supported");
+        return fail(Error::NotSupported("Default ACL not supported".to_string()));
     }
 
     if !xacl_is_posix(acl) || is_nfs4 {
@@ -208,6 +210,72 @@ pub fn xacl_set_file(
     Ok(())
 }
 
+/// Get ACL from an open file descriptor.
+///
+/// Like `xacl_get_file`, this code first tries to obtain the Posix.1e ACL
+/// before falling back to the NFS4 ACL.
+pub fn xacl_get_fd(fd: RawFd, default_acl: bool) -> io::Result<acl_t> {
+    let mut acl_type = get_acl_type(ptr::null_mut(), default_acl);
+    let acl = unsafe { acl_get_fd_np(fd, acl_type) };
+
+    if !acl.is_null() {
+        return Ok(acl);
+    }
+
+    // `acl_get_fd_np` returns EINVAL when the ACL type is not appropriate for
+    // the file system object. Retry with NFSv4 type.
+    if io::Error::last_os_error().raw_os_error() == Some(sg::EINVAL) {
+        // NFSv4 does not support default ACL.
+        if default_acl {
+            return fail(Error::NotSupported("Default ACL not supported".to_string()));
+        }
+
+        acl_type = sg::ACL_TYPE_NFS4;
+        let nfs_acl = unsafe { acl_get_fd_np(fd, acl_type) };
+        if !nfs_acl.is_null() {
+            return Ok(nfs_acl);
+        }
+    }
+
+    // Report acl_type and file descriptor that failed.
+    let func = match acl_type {
+        sg::ACL_TYPE_ACCESS => "acl_get_fd_np/access",
+        sg::ACL_TYPE_DEFAULT => "acl_get_fd_np/default",
+        sg::ACL_TYPE_NFS4 => "acl_get_fd_np/nfs4",
+        _ => "acl_get_fd_np/?",
+    };
+
+    fail_err("null", func, fd)
+}
+
+/// Set ACL for an open file descriptor.
+pub fn xacl_set_fd(fd: RawFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    log_brand("xacl_set_fd", acl)?;
+
+    if default_acl && xacl_is_empty(acl) {
+        // Special case to delete the ACL.
+        let ret = unsafe { acl_delete_fd_np(fd, sg::ACL_TYPE_DEFAULT) };
+        if ret != 0 {
+            return fail_err(ret, "acl_delete_fd_np", fd);
+        }
+        return Ok(());
+    }
+
+    let acl_type = get_acl_type(acl, default_acl);
+    let ret = unsafe { acl_set_fd_np(fd, acl, acl_type) };
+    if ret != 0 {
+        let func = match acl_type {
+            sg::ACL_TYPE_ACCESS => "acl_set_fd_np/access",
+            sg::ACL_TYPE_DEFAULT => "acl_set_fd_np/default",
+            sg::ACL_TYPE_NFS4 => "acl_set_fd_np/nfs4",
+            _ => "acl_set_fd_np/?",
+        };
+        return fail_err(ret, func, fd);
+    }
+
+    Ok(())
+}
+
 fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
     let tag = xacl_get_tag_type(entry)?;
 
@@ -353,7 +421,7 @@ pub fn xacl_set_tag_qualifier(
             xacl_set_tag_type(entry, sg::ACL_EVERYONE)?;
         }
         Qualifier::Unknown(tag) => {
-            return fail_custom(&format!("unknown tag: {tag}"));
+            return fail(Error::InvalidEntry(format!("unknown tag: {tag}")));
         }
     }
 
@@ -401,12 +469,10 @@ pub fn xacl_add_entry(
         xacl_foreach(*acl, |entry| {
             let (_, prev) = xacl_get_tag_qualifier(*acl, entry)?;
             if prev == *qualifier {
-                let default = if flags.contains(Flag::DEFAULT) {
-                    "default "
-                } else {
-                    ""
-                };
-                fail_custom(&format!("duplicate {default}entry for \"{prev}\""))?;
+                fail(Error::DuplicateEntry {
+                    entry: prev.to_string(),
+                    default: flags.contains(Flag::DEFAULT),
+                })?;
             }
             Ok(())
         })?;
@@ -482,6 +548,7 @@ pub fn xacl_is_nfs4(path: &Path, symlink: bool) -> io::Result<bool> {
 #[cfg(test)]
 mod util_freebsd_test {
     use super::*;
+    use crate::resolver::NssResolver;
 
     #[test]
     fn test_acl_api_misuse() {
@@ -491,11 +558,11 @@ mod util_freebsd_test {
 
         // Setting tag other than 1 or 2 results in EINVAL error.
         let err = xacl_set_tag_type(entry, 0).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::EINVAL));
+        assert_eq!(errno(&err), Some(sg::EINVAL));
 
         // Setting qualifier without first setting tag to a valid value results in EINVAL.
         let err = xacl_set_qualifier(entry, 500).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::EINVAL));
+        assert_eq!(errno(&err), Some(sg::EINVAL));
 
         // Try to set entry using unknown qualifier -- this should fail.
         let err =
@@ -548,7 +615,7 @@ mod util_freebsd_test {
         let entry_p = xacl_create_entry(&mut acl).unwrap();
 
         let (allow, qualifier) = xacl_get_tag_qualifier(acl, entry_p).unwrap();
-        assert_eq!(qualifier.name().unwrap(), "@tag 0");
+        assert_eq!(qualifier.name(&NssResolver).unwrap(), "@tag 0");
         // FreeBSD: Unbranded entry is treated as Posix.
         assert!(allow);
 
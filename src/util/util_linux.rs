@@ -1,3 +1,4 @@
+use crate::error::Error;
 use crate::failx::*;
 use crate::flag::Flag;
 use crate::perm::Perm;
@@ -9,6 +10,7 @@ use scopeguard::defer;
 use std::ffi::{c_void, CString};
 use std::io;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 
 pub use util_common::{xacl_create_entry, xacl_foreach, xacl_free, xacl_init, xacl_is_empty};
@@ -25,7 +27,9 @@ const fn get_acl_type(default_acl: bool) -> acl_type_t {
 
 pub fn xacl_get_file(path: &Path, symlink_acl: bool, default_acl: bool) -> io::Result<acl_t> {
     if symlink_acl {
-        return fail_custom("Linux does not support symlinks with ACL's.");
+        return fail(Error::NotSupported(
+            "Linux does not support symlinks with ACL's.".to_string(),
+        ));
     }
 
     let acl_type = get_acl_type(default_acl);
@@ -51,7 +55,9 @@ pub fn xacl_set_file(
     default_acl: bool,
 ) -> io::Result<()> {
     if symlink_acl {
-        return fail_custom("Linux does not support symlinks with ACL's");
+        return fail(Error::NotSupported(
+            "Linux does not support symlinks with ACL's".to_string(),
+        ));
     }
 
     let c_path = CString::new(path.as_os_str().as_bytes())?;
@@ -69,6 +75,55 @@ pub fn xacl_set_file(
     Ok(())
 }
 
+/// Return the path to an open file descriptor in the `/proc` file system.
+///
+/// libacl does not provide a file descriptor API for the default ACL. The
+/// `/proc/self/fd` magic link refers to the open file itself, so using it does
+/// not re-resolve the original path.
+fn proc_fd_path(fd: RawFd) -> io::Result<CString> {
+    Ok(CString::new(format!("/proc/self/fd/{fd}"))?)
+}
+
+pub fn xacl_get_fd(fd: RawFd, default_acl: bool) -> io::Result<acl_t> {
+    let acl = if default_acl {
+        let c_path = proc_fd_path(fd)?;
+        unsafe { acl_get_file(c_path.as_ptr(), sg::ACL_TYPE_DEFAULT) }
+    } else {
+        unsafe { acl_get_fd(fd) }
+    };
+
+    if acl.is_null() {
+        let func = if default_acl {
+            "acl_get_file/default"
+        } else {
+            "acl_get_fd"
+        };
+        return fail_err("null", func, fd);
+    }
+
+    Ok(acl)
+}
+
+pub fn xacl_set_fd(fd: RawFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    let ret = if default_acl {
+        let c_path = proc_fd_path(fd)?;
+        unsafe { acl_set_file(c_path.as_ptr(), sg::ACL_TYPE_DEFAULT, acl) }
+    } else {
+        unsafe { acl_set_fd(fd, acl) }
+    };
+
+    if ret != 0 {
+        let func = if default_acl {
+            "acl_set_file/default"
+        } else {
+            "acl_set_fd"
+        };
+        return fail_err(ret, func, fd);
+    }
+
+    Ok(())
+}
+
 fn xacl_get_qualifier(entry: acl_entry_t) -> io::Result<Qualifier> {
     let tag = xacl_get_tag_type(entry)?;
 
@@ -131,7 +186,9 @@ pub fn xacl_set_tag_qualifier(
     qualifier: &Qualifier,
 ) -> io::Result<()> {
     if !allow {
-        return fail_custom("allow=false is not supported on Linux");
+        return fail(Error::NotSupported(
+            "allow=false is not supported on Linux".to_string(),
+        ));
     }
 
     match qualifier {
@@ -156,7 +213,7 @@ pub fn xacl_set_tag_qualifier(
             xacl_set_tag_type(entry, sg::ACL_MASK)?;
         }
         Qualifier::Unknown(tag) => {
-            return fail_custom(&format!("unknown tag: {tag}"));
+            return fail(Error::InvalidEntry(format!("unknown tag: {tag}")));
         }
     }
 
@@ -179,12 +236,10 @@ pub fn xacl_add_entry(
     xacl_foreach(*acl, |entry| {
         let (_, prev) = xacl_get_tag_qualifier(*acl, entry)?;
         if prev == *qualifier {
-            let default = if flags.contains(Flag::DEFAULT) {
-                "default "
-            } else {
-                ""
-            };
-            fail_custom(&format!("duplicate {default}entry for \"{prev}\""))?;
+            fail(Error::DuplicateEntry {
+                entry: prev.to_string(),
+                default: flags.contains(Flag::DEFAULT),
+            })?;
         }
         Ok(())
     })?;
@@ -204,6 +259,7 @@ pub const fn xacl_is_posix(_acl: acl_t) -> bool {
 #[cfg(test)]
 mod util_linux_test {
     use super::*;
+    use crate::resolver::NssResolver;
 
     #[test]
     fn test_acl_api_misuse() {
@@ -213,11 +269,11 @@ mod util_linux_test {
 
         // Setting tag other than 1 or 2 results in EINVAL error.
         let err = xacl_set_tag_type(entry, 0).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::EINVAL));
+        assert_eq!(errno(&err), Some(sg::EINVAL));
 
         // Setting qualifier without first setting tag to a valid value results in EINVAL.
         let err = xacl_set_qualifier(entry, 500).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::EINVAL));
+        assert_eq!(errno(&err), Some(sg::EINVAL));
 
         // Try to set entry using unknown qualifier -- this should fail.
         let err =
@@ -267,7 +323,7 @@ mod util_linux_test {
         let entry_p = xacl_create_entry(&mut acl).unwrap();
 
         let (allow, qualifier) = xacl_get_tag_qualifier(acl, entry_p).unwrap();
-        assert_eq!(qualifier.name().unwrap(), "@tag 0");
+        assert_eq!(qualifier.name(&NssResolver).unwrap(), "@tag 0");
         assert!(allow);
 
         xacl_free(acl);
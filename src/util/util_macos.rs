@@ -1,4 +1,5 @@
 use crate::bititer::BitIter;
+use crate::error::Error;
 use crate::failx::*;
 use crate::flag::Flag;
 use crate::perm::Perm;
@@ -10,6 +11,7 @@ use scopeguard::defer;
 use std::ffi::{c_void, CString};
 use std::io;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 use uuid::Uuid;
 
@@ -32,7 +34,9 @@ fn path_exists(path: &Path, symlink_only: bool) -> bool {
 /// get the ACL from the symlink itself (true) or the file it points to (false).
 pub fn xacl_get_file(path: &Path, symlink_acl: bool, default_acl: bool) -> io::Result<acl_t> {
     if default_acl {
-        return fail_custom("macOS does not support default ACL");
+        return fail(Error::NotSupported(
+            "macOS does not support default ACL".to_string(),
+        ));
     }
 
     let c_path = CString::new(path.as_os_str().as_bytes())?;
@@ -52,7 +56,7 @@ pub fn xacl_get_file(path: &Path, symlink_acl: bool, default_acl: bool) -> io::R
 
         // acl_get_file et al. can return NULL (ENOENT) if the file exists, but
         // there is no ACL. If the path exists, return an *empty* ACL.
-        if err.raw_os_error() == Some(sg::ENOENT) && path_exists(path, symlink_acl) {
+        if errno(&err) == Some(sg::ENOENT) && path_exists(path, symlink_acl) {
             return xacl_init(1);
         }
 
@@ -85,7 +89,9 @@ pub fn xacl_set_file(
     default_acl: bool,
 ) -> io::Result<()> {
     if default_acl {
-        return fail_custom("macOS does not support default ACL");
+        return fail(Error::NotSupported(
+            "macOS does not support default ACL".to_string(),
+        ));
     }
 
     let c_path = CString::new(path.as_os_str().as_bytes())?;
@@ -100,7 +106,7 @@ pub fn xacl_set_file(
 
         // acl_set_link_np() returns ENOTSUP for symlinks. Work-around this
         // by using acl_set_fd().
-        if err.raw_os_error() == Some(sg::ENOTSUP) && symlink_acl {
+        if errno(&err) == Some(sg::ENOTSUP) && symlink_acl {
             return xacl_set_file_symlink_alt(&c_path, acl);
         }
 
@@ -110,6 +116,46 @@ pub fn xacl_set_file(
     Ok(())
 }
 
+/// Get the native ACL for an open file descriptor.
+pub fn xacl_get_fd(fd: RawFd, default_acl: bool) -> io::Result<acl_t> {
+    if default_acl {
+        return fail(Error::NotSupported(
+            "macOS does not support default ACL".to_string(),
+        ));
+    }
+
+    let acl = unsafe { acl_get_fd_np(fd, acl_type_t_ACL_TYPE_EXTENDED) };
+    if acl.is_null() {
+        let err = log_err("null", "acl_get_fd_np", fd);
+
+        // acl_get_fd_np can return NULL (ENOENT) if there is no ACL. Return
+        // an *empty* ACL.
+        if errno(&err) == Some(sg::ENOENT) {
+            return xacl_init(1);
+        }
+
+        return Err(err);
+    }
+
+    Ok(acl)
+}
+
+/// Set the ACL for an open file descriptor.
+pub fn xacl_set_fd(fd: RawFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    if default_acl {
+        return fail(Error::NotSupported(
+            "macOS does not support default ACL".to_string(),
+        ));
+    }
+
+    let ret = unsafe { acl_set_fd_np(fd, acl, acl_type_t_ACL_TYPE_EXTENDED) };
+    if ret != 0 {
+        return fail_err(ret, "acl_set_fd_np", fd);
+    }
+
+    Ok(())
+}
+
 /// Get the GUID qualifier and resolve it to a User/Group if possible.
 ///
 /// Only call this function for `ACL_EXTENDED_ALLOW` or `ACL_EXTENDED_DENY`.
@@ -261,6 +307,7 @@ pub const fn xacl_is_posix(_acl: acl_t) -> bool {
 #[cfg(test)]
 mod util_macos_test {
     use super::*;
+    use crate::resolver::NssResolver;
 
     #[test]
     fn test_acl_init() {
@@ -287,7 +334,7 @@ mod util_macos_test {
 
         // Memory error if we try to allocate MAX_ENTRIES + 1.
         let err = xacl_create_entry(&mut acl).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::ENOMEM));
+        assert_eq!(errno(&err), Some(sg::ENOMEM));
 
         xacl_free(acl);
     }
@@ -299,11 +346,11 @@ mod util_macos_test {
 
         // Setting tag other than 1 or 2 results in EINVAL error.
         let err = xacl_set_tag_type(entry, 0).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::EINVAL));
+        assert_eq!(errno(&err), Some(sg::EINVAL));
 
         // Setting qualifier without first setting tag to a valid value results in EINVAL.
         let err = xacl_set_qualifier(entry, &Qualifier::Guid(Uuid::nil())).unwrap_err();
-        assert_eq!(err.raw_os_error(), Some(sg::EINVAL));
+        assert_eq!(errno(&err), Some(sg::EINVAL));
 
         let entry2 = xacl_create_entry(&mut acl).unwrap();
         xacl_set_tag_type(entry2, 1).unwrap();
@@ -317,7 +364,7 @@ mod util_macos_test {
         let entry_p = xacl_create_entry(&mut acl).unwrap();
 
         let (allow, qualifier) = xacl_get_tag_qualifier(acl, entry_p).unwrap();
-        assert_eq!(qualifier.name().unwrap(), "@tag 0");
+        assert_eq!(qualifier.name(&NssResolver).unwrap(), "@tag 0");
         assert!(!allow);
 
         xacl_free(acl);
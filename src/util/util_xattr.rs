@@ -0,0 +1,395 @@
+//! Implements the `xacl_*` functions on Linux without libacl.
+//!
+//! ACL's are read and written directly as the `system.posix_acl_access` and
+//! `system.posix_acl_default` extended attributes. A native ACL is a boxed
+//! vector of entries in the kernel's binary format, kept sorted in the order
+//! the kernel expects.
+
+#![allow(non_camel_case_types)]
+
+use crate::error::Error;
+use crate::failx::*;
+use crate::flag::Flag;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::sys::{fgetxattr, fremovexattr, fsetxattr, getxattr, removexattr, setxattr, sg};
+use crate::xattr::*;
+
+use std::ffi::{c_void, CString};
+use std::fs::{self, File};
+use std::io;
+use std::mem::ManuallyDrop;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::Path;
+use std::ptr;
+
+/// Native ACL.
+pub type acl_t = *mut Vec<XattrEntry>;
+
+/// Native ACL entry.
+pub type acl_entry_t = *mut XattrEntry;
+
+/// Size of the initial buffer used to read an extended attribute. This is
+/// enough for 16 entries.
+const INITIAL_BUFSIZE: usize = 132;
+
+/// Free memory allocated by `xacl_init`.
+pub fn xacl_free(acl: acl_t) {
+    assert!(!acl.is_null());
+    drop(unsafe { Box::from_raw(acl) });
+}
+
+/// Return true if acl is empty.
+pub fn xacl_is_empty(acl: acl_t) -> bool {
+    unsafe { (*acl).is_empty() }
+}
+
+/// Iterate over entries in a native ACL.
+pub fn xacl_foreach<F: FnMut(acl_entry_t) -> io::Result<()>>(
+    acl: acl_t,
+    mut func: F,
+) -> io::Result<()> {
+    assert!(!acl.is_null());
+    for entry in unsafe { (*acl).iter_mut() } {
+        func(entry)?;
+    }
+
+    Ok(())
+}
+
+/// Create a new empty ACL with the given capacity.
+///
+/// Client must call `xacl_free` when done with result.
+#[allow(clippy::unnecessary_wraps)]
+pub fn xacl_init(capacity: usize) -> io::Result<acl_t> {
+    Ok(Box::into_raw(Box::new(Vec::with_capacity(capacity))))
+}
+
+/// Return an ACL built from a list of entries.
+fn xacl_from_entries(entries: Vec<XattrEntry>) -> acl_t {
+    let mut entries = entries;
+    entries.sort_by_key(|entry| entry.sort_key());
+    Box::into_raw(Box::new(entries))
+}
+
+/// Return the minimal access ACL equivalent to a file's mode.
+fn xacl_from_mode(mode: u32) -> acl_t {
+    #[allow(clippy::cast_possible_truncation)]
+    let entry = |tag, shift: u32| XattrEntry {
+        tag,
+        perm: ((mode >> shift) & 7) as u16,
+        id: ACL_UNDEFINED_ID,
+    };
+
+    xacl_from_entries(vec![
+        entry(ACL_USER_OBJ, 6),
+        entry(ACL_GROUP_OBJ, 3),
+        entry(ACL_OTHER, 0),
+    ])
+}
+
+const fn get_xattr_name(default_acl: bool) -> &'static str {
+    if default_acl {
+        ACL_EA_DEFAULT
+    } else {
+        ACL_EA_ACCESS
+    }
+}
+
+/// Return a permission denied error.
+///
+/// libacl returns this error when asked for the default ACL of a
+/// non-directory. The kernel would return an empty result instead.
+fn fail_non_directory<T, U: std::fmt::Debug>(func: &str, arg: U) -> io::Result<T> {
+    fail_from_err(sg::EACCES, func, arg)
+}
+
+/// Read an extended attribute's value using the provided `getxattr` function.
+///
+/// Returns `None` if the extended attribute does not exist.
+fn read_xattr<F>(mut getxattr_fn: F) -> io::Result<Option<Vec<u8>>>
+where
+    F: FnMut(*mut c_void, usize) -> isize,
+{
+    let mut buf = Vec::<u8>::with_capacity(INITIAL_BUFSIZE);
+
+    loop {
+        let ret = getxattr_fn(buf.as_mut_ptr().cast::<c_void>(), buf.capacity());
+        if let Ok(len) = usize::try_from(ret) {
+            unsafe { buf.set_len(len) };
+            return Ok(Some(buf));
+        }
+
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(sg::ENODATA) => return Ok(None),
+            Some(sg::ERANGE) => {
+                // Ask for the required size and try again.
+                let size = getxattr_fn(ptr::null_mut(), 0);
+                if size < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                #[allow(clippy::cast_sign_loss)]
+                buf.reserve(size as usize);
+            }
+            _ => return Err(err),
+        }
+    }
+}
+
+/// Return native ACL from an extended attribute's value.
+///
+/// If the extended attribute is missing, return the ACL equivalent to `mode`
+/// for the access ACL, or an empty ACL for the default ACL.
+fn xacl_from_xattr(value: Option<Vec<u8>>, default_acl: bool, mode: u32) -> io::Result<acl_t> {
+    match value {
+        Some(data) => Ok(xacl_from_entries(decode_entries(&data)?)),
+        None if default_acl => xacl_init(1),
+        None => Ok(xacl_from_mode(mode)),
+    }
+}
+
+pub fn xacl_get_file(path: &Path, symlink_acl: bool, default_acl: bool) -> io::Result<acl_t> {
+    if symlink_acl {
+        return fail(Error::NotSupported(
+            "Linux does not support symlinks with ACL's.".to_string(),
+        ));
+    }
+
+    let func = if default_acl {
+        "getxattr/default"
+    } else {
+        "getxattr/access"
+    };
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let meta = fs::metadata(path).map_err(|err| log_from_err(err_code(&err), func, &c_path))?;
+    if default_acl && !meta.is_dir() {
+        return fail_non_directory(func, &c_path);
+    }
+
+    let c_name = CString::new(get_xattr_name(default_acl))?;
+    let value = read_xattr(|value, size| unsafe {
+        getxattr(c_path.as_ptr(), c_name.as_ptr(), value, size)
+    })
+    .map_err(|err| log_from_err(err_code(&err), func, &c_path))?;
+
+    xacl_from_xattr(value, default_acl, meta.permissions().mode())
+}
+
+pub fn xacl_set_file(
+    path: &Path,
+    acl: acl_t,
+    symlink_acl: bool,
+    default_acl: bool,
+) -> io::Result<()> {
+    if symlink_acl {
+        return fail(Error::NotSupported(
+            "Linux does not support symlinks with ACL's".to_string(),
+        ));
+    }
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let c_name = CString::new(get_xattr_name(default_acl))?;
+
+    if default_acl && xacl_is_empty(acl) {
+        // An empty default ACL is deleted.
+        let ret = unsafe { removexattr(c_path.as_ptr(), c_name.as_ptr()) };
+        if ret != 0 && io::Error::last_os_error().raw_os_error() != Some(sg::ENODATA) {
+            return fail_err(ret, "removexattr/default", &c_path);
+        }
+        return Ok(());
+    }
+
+    let data = encode_entries(unsafe { &*acl });
+    let ret = unsafe {
+        setxattr(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            data.as_ptr().cast::<c_void>(),
+            data.len(),
+            0,
+        )
+    };
+    if ret != 0 {
+        let func = if default_acl {
+            "setxattr/default"
+        } else {
+            "setxattr/access"
+        };
+        return fail_err(ret, func, &c_path);
+    }
+
+    Ok(())
+}
+
+pub fn xacl_get_fd(fd: RawFd, default_acl: bool) -> io::Result<acl_t> {
+    let func = if default_acl {
+        "fgetxattr/default"
+    } else {
+        "fgetxattr/access"
+    };
+
+    // Borrow the file descriptor to call `fstat`.
+    let file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    let meta = file
+        .metadata()
+        .map_err(|err| log_from_err(err_code(&err), func, fd))?;
+    if default_acl && !meta.is_dir() {
+        return fail_non_directory(func, fd);
+    }
+
+    let c_name = CString::new(get_xattr_name(default_acl))?;
+    let value = read_xattr(|value, size| unsafe { fgetxattr(fd, c_name.as_ptr(), value, size) })
+        .map_err(|err| log_from_err(err_code(&err), func, fd))?;
+
+    xacl_from_xattr(value, default_acl, meta.permissions().mode())
+}
+
+pub fn xacl_set_fd(fd: RawFd, acl: acl_t, default_acl: bool) -> io::Result<()> {
+    let c_name = CString::new(get_xattr_name(default_acl))?;
+
+    if default_acl && xacl_is_empty(acl) {
+        // An empty default ACL is deleted.
+        let ret = unsafe { fremovexattr(fd, c_name.as_ptr()) };
+        if ret != 0 && io::Error::last_os_error().raw_os_error() != Some(sg::ENODATA) {
+            return fail_err(ret, "fremovexattr/default", fd);
+        }
+        return Ok(());
+    }
+
+    let data = encode_entries(unsafe { &*acl });
+    let ret = unsafe {
+        fsetxattr(
+            fd,
+            c_name.as_ptr(),
+            data.as_ptr().cast::<c_void>(),
+            data.len(),
+            0,
+        )
+    };
+    if ret != 0 {
+        let func = if default_acl {
+            "fsetxattr/default"
+        } else {
+            "fsetxattr/access"
+        };
+        return fail_err(ret, func, fd);
+    }
+
+    Ok(())
+}
+
+/// Return the OS error code of an error, or EINVAL if it doesn't have one.
+fn err_code(err: &io::Error) -> i32 {
+    err.raw_os_error().unwrap_or(sg::EINVAL)
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub fn xacl_get_entry(
+    _acl: acl_t,
+    entry: acl_entry_t,
+) -> io::Result<(bool, Qualifier, Perm, Flag)> {
+    let entry = unsafe { *entry };
+    let qualifier = entry.qualifier();
+    let perms = Perm::from_bits_truncate(u32::from(entry.perm));
+
+    Ok((true, qualifier, perms, Flag::empty()))
+}
+
+pub fn xacl_add_entry(
+    acl: &mut acl_t,
+    allow: bool,
+    qualifier: &Qualifier,
+    perms: Perm,
+    flags: Flag,
+) -> io::Result<()> {
+    if !allow {
+        return fail(Error::NotSupported(
+            "allow=false is not supported on Linux".to_string(),
+        ));
+    }
+
+    let xentry = XattrEntry::new(qualifier, perms)?;
+    let entries = unsafe { &mut **acl };
+
+    // Check for duplicates already in the list, and find where the new entry
+    // belongs in sorted order.
+    let Err(pos) = entries.binary_search_by_key(&xentry.sort_key(), |entry| entry.sort_key())
+    else {
+        return fail(Error::DuplicateEntry {
+            entry: qualifier.to_string(),
+            default: flags.contains(Flag::DEFAULT),
+        });
+    };
+
+    entries.insert(pos, xentry);
+
+    Ok(())
+}
+
+pub const fn xacl_is_posix(_acl: acl_t) -> bool {
+    true
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod util_xattr_test {
+    use super::*;
+
+    #[test]
+    fn test_add_entry_sorted() {
+        let mut acl = xacl_init(4).unwrap();
+        let rw = Perm::READ | Perm::WRITE;
+
+        xacl_add_entry(&mut acl, true, &Qualifier::Other, rw, Flag::empty()).unwrap();
+        xacl_add_entry(&mut acl, true, &Qualifier::User(600), rw, Flag::empty()).unwrap();
+        xacl_add_entry(&mut acl, true, &Qualifier::UserObj, rw, Flag::empty()).unwrap();
+        xacl_add_entry(&mut acl, true, &Qualifier::User(500), rw, Flag::empty()).unwrap();
+
+        let tags = unsafe { (*acl).iter().map(|e| (e.tag, e.id)).collect::<Vec<_>>() };
+        assert_eq!(
+            tags,
+            vec![
+                (ACL_USER_OBJ, ACL_UNDEFINED_ID),
+                (ACL_USER, 500),
+                (ACL_USER, 600),
+                (ACL_OTHER, ACL_UNDEFINED_ID)
+            ]
+        );
+
+        let err =
+            xacl_add_entry(&mut acl, true, &Qualifier::User(500), rw, Flag::DEFAULT).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate default entry for \"user:500\"");
+
+        let err = xacl_add_entry(&mut acl, false, &Qualifier::Mask, rw, Flag::empty()).unwrap_err();
+        assert_eq!(err.to_string(), "allow=false is not supported on Linux");
+
+        xacl_free(acl);
+    }
+
+    #[test]
+    fn test_get_file_without_xattr() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o640)).unwrap();
+
+        let acl = xacl_get_file(file.path(), false, false).unwrap();
+        let entries = unsafe { (*acl).clone() };
+        assert_eq!(
+            entries.iter().map(|e| (e.tag, e.perm)).collect::<Vec<_>>(),
+            vec![(ACL_USER_OBJ, 6), (ACL_GROUP_OBJ, 4), (ACL_OTHER, 0)]
+        );
+        xacl_free(acl);
+
+        let err = xacl_get_file(file.path(), false, true).unwrap_err();
+        assert_eq!(errno(&err), Some(sg::EACCES));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let acl = xacl_get_file(dir.path(), false, true).unwrap();
+        assert!(xacl_is_empty(acl));
+        xacl_free(acl);
+    }
+}
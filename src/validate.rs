@@ -0,0 +1,360 @@
+//! Implements validation of Posix.1e ACL entries.
+
+use crate::aclentry::{AclEntry, AclEntryKind};
+use crate::error::Error;
+use crate::flag::Flag;
+#[cfg(test)]
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::resolver::{NssResolver, PrincipalResolver};
+
+use bitflags::bitflags;
+use std::fmt;
+
+bitflags! {
+    /// Controls how entries are validated.
+    #[derive(Default)]
+    pub struct ValidateOption : u32 {
+        /// Entries are for a non-directory. Default entries are reported.
+        const NON_DIRECTORY = 0b0001;
+    }
+}
+
+/// Kind of problem found by [`validate`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// A required entry (owner, owning group or other) is missing.
+    MissingEntry,
+
+    /// Another entry has the same principal.
+    DuplicateEntry,
+
+    /// A user or group name can't be resolved.
+    UnknownPrincipal,
+
+    /// The entry is not valid, e.g. it has an unknown kind.
+    InvalidEntry,
+
+    /// The entry denies access, which is not supported on Linux.
+    DenyEntry,
+
+    /// The entry is for the default ACL, but the file is not a directory.
+    DefaultOnNonDirectory,
+
+    /// The mask entry hides some of a named user or group entry's
+    /// permissions.
+    MaskHidesPerms,
+}
+
+/// Problem found by [`validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// Index of the entry with the problem. None if the problem is with the
+    /// ACL as a whole, e.g. a missing entry.
+    pub index: Option<usize>,
+
+    /// Kind of problem.
+    pub kind: ValidationIssueKind,
+
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl ValidationIssue {
+    const fn new(
+        index: Option<usize>,
+        kind: ValidationIssueKind,
+        message: String,
+    ) -> ValidationIssue {
+        ValidationIssue {
+            index,
+            kind,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.index {
+            Some(i) => write!(f, "entry {}: {}", i, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Return "default " for a default entry, for use in messages.
+const fn default_str(is_default: bool) -> &'static str {
+    if is_default {
+        "default "
+    } else {
+        ""
+    }
+}
+
+/// Check entries and report every problem found.
+///
+/// The entries are checked as [`setfacl`](crate::setfacl) would check them
+/// when called with no options: the access ACL must have entries for the
+/// owner, owning group and other. The default ACL (entries with
+/// [`Flag::DEFAULT`]) may be empty; otherwise it needs the same entries.
+///
+/// Besides the errors that would make `setfacl` fail, this also reports a
+/// mask entry that hides permissions given to the group class entries.
+///
+/// # Errors
+///
+/// Returns the list of problems found, in order of entry index. Problems with
+/// the ACL as a whole come last.
+pub fn validate<O>(entries: &[AclEntry], options: O) -> Result<(), Vec<ValidationIssue>>
+where
+    O: Into<Option<ValidateOption>>,
+{
+    validate_with(entries, options, &NssResolver)
+}
+
+/// Check entries and report every problem found, using `resolver` to convert
+/// user/group names to uid/gid.
+///
+/// Works like [`validate`], which uses [`NssResolver`](crate::NssResolver).
+///
+/// # Errors
+///
+/// Returns the list of problems found, in order of entry index. Problems with
+/// the ACL as a whole come last.
+pub fn validate_with<O, R>(
+    entries: &[AclEntry],
+    options: O,
+    resolver: &R,
+) -> Result<(), Vec<ValidationIssue>>
+where
+    O: Into<Option<ValidateOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let options = options.into().unwrap_or_default();
+    let mut issues = Vec::new();
+    let mut seen = Vec::<(Qualifier, bool)>::with_capacity(entries.len());
+
+    for (i, entry) in entries.iter().enumerate() {
+        let is_default = entry.flags.contains(Flag::DEFAULT);
+
+        #[cfg(target_os = "linux")]
+        if !entry.allow {
+            issues.push(ValidationIssue::new(
+                Some(i),
+                ValidationIssueKind::DenyEntry,
+                "allow=false is not supported on Linux".to_string(),
+            ));
+        }
+
+        if is_default && options.contains(ValidateOption::NON_DIRECTORY) {
+            issues.push(ValidationIssue::new(
+                Some(i),
+                ValidationIssueKind::DefaultOnNonDirectory,
+                "Non-directory does not have default ACL".to_string(),
+            ));
+        }
+
+        match entry.qualifier(&resolver) {
+            Ok(qualifier) => {
+                let key = (qualifier, is_default);
+                if seen.contains(&key) {
+                    issues.push(ValidationIssue::new(
+                        Some(i),
+                        ValidationIssueKind::DuplicateEntry,
+                        Error::DuplicateEntry {
+                            entry: key.0.to_string(),
+                            default: is_default,
+                        }
+                        .to_string(),
+                    ));
+                } else {
+                    seen.push(key);
+                }
+            }
+            Err(err) => {
+                let kind = match entry.kind {
+                    AclEntryKind::User | AclEntryKind::Group => {
+                        ValidationIssueKind::UnknownPrincipal
+                    }
+                    _ => ValidationIssueKind::InvalidEntry,
+                };
+                issues.push(ValidationIssue::new(Some(i), kind, err.to_string()));
+            }
+        }
+    }
+
+    for is_default in [false, true] {
+        check_mask(entries, is_default, &mut issues);
+    }
+    issues.sort_by_key(|issue| issue.index);
+
+    for is_default in [false, true] {
+        check_missing(entries, is_default, &mut issues);
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Report named user and group entries whose permissions are hidden by the
+/// mask. The owning group is not reported, since `chmod` narrows the mask
+/// without changing the owning group entry.
+fn check_mask(entries: &[AclEntry], is_default: bool, issues: &mut Vec<ValidationIssue>) {
+    let half = || {
+        entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, e)| e.flags.contains(Flag::DEFAULT) == is_default)
+    };
+
+    let Some(mask) = half()
+        .find(|(_, e)| e.kind == AclEntryKind::Mask)
+        .map(|(_, e)| e.perms)
+    else {
+        return;
+    };
+
+    for (i, entry) in half() {
+        if !matches!(entry.kind, AclEntryKind::User | AclEntryKind::Group) || entry.name.is_empty()
+        {
+            continue;
+        }
+
+        let hidden = entry.perms - mask;
+        if !hidden.is_empty() {
+            issues.push(ValidationIssue::new(
+                Some(i),
+                ValidationIssueKind::MaskHidesPerms,
+                format!(
+                    "{}mask hides permissions \"{}\"",
+                    default_str(is_default),
+                    hidden
+                ),
+            ));
+        }
+    }
+}
+
+/// Report required entries that are missing.
+fn check_missing(entries: &[AclEntry], is_default: bool, issues: &mut Vec<ValidationIssue>) {
+    let half = entries
+        .iter()
+        .filter(|e| e.flags.contains(Flag::DEFAULT) == is_default)
+        .collect::<Vec<_>>();
+
+    // The default ACL may be empty.
+    if is_default && half.is_empty() {
+        return;
+    }
+
+    let required = [AclEntryKind::User, AclEntryKind::Group, AclEntryKind::Other];
+    for kind in required {
+        let found = half
+            .iter()
+            .any(|e| e.kind == kind && (e.name.is_empty() || kind == AclEntryKind::Other));
+        if !found {
+            issues.push(ValidationIssue::new(
+                None,
+                ValidationIssueKind::MissingEntry,
+                Error::MissingRequiredEntry {
+                    kind,
+                    default: is_default,
+                }
+                .to_string(),
+            ));
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+
+    fn kinds(
+        result: Result<(), Vec<ValidationIssue>>,
+    ) -> Vec<(Option<usize>, ValidationIssueKind)> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|issue| (issue.index, issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let entries =
+            crate::from_str("u::rwx\nu:500:r\ng::r\nm::r\no::\nd:u::rwx\nd:g::r\nd:o::").unwrap();
+        assert_eq!(validate(&entries, None), Ok(()));
+
+        // The mask may hide permissions of the owning group, e.g. after chmod.
+        let entries = crate::from_str("u::rwx\nu:500:r\ng::rwx\nm::r\no::").unwrap();
+        assert_eq!(validate(&entries, None), Ok(()));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut entries = crate::from_str(
+            "u::rwx
+            u:500:rw
+            u:500:r
+            g:non_existant_group:r
+            m::r
+            d:u::rwx
+            d:g::r",
+        )
+        .unwrap();
+        entries.push(AclEntry::allow_mask(Perm::READ, Flag::DEFAULT));
+        entries[5].name = "x".to_string();
+
+        let result = validate(&entries, ValidateOption::NON_DIRECTORY);
+        let issues = result.clone().unwrap_err();
+        assert_eq!(
+            issues[0].to_string(),
+            "entry 1: mask hides permissions \"write\""
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "entry 2: duplicate entry for \"user:500\""
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "entry 3: unknown group name: \"non_existant_group\""
+        );
+
+        assert_eq!(
+            kinds(result),
+            vec![
+                (Some(1), ValidationIssueKind::MaskHidesPerms),
+                (Some(2), ValidationIssueKind::DuplicateEntry),
+                (Some(3), ValidationIssueKind::UnknownPrincipal),
+                (Some(5), ValidationIssueKind::DefaultOnNonDirectory),
+                (Some(5), ValidationIssueKind::UnknownPrincipal),
+                (Some(5), ValidationIssueKind::MaskHidesPerms),
+                (Some(6), ValidationIssueKind::DefaultOnNonDirectory),
+                (Some(7), ValidationIssueKind::DefaultOnNonDirectory),
+                (None, ValidationIssueKind::MissingEntry),
+                (None, ValidationIssueKind::MissingEntry),
+                (None, ValidationIssueKind::MissingEntry),
+                (None, ValidationIssueKind::MissingEntry),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_validate_deny() {
+        let mut entries = crate::from_mode(0o644);
+        entries[1].allow = false;
+
+        assert_eq!(
+            kinds(validate(&entries, None)),
+            vec![(Some(1), ValidationIssueKind::DenyEntry)]
+        );
+    }
+}
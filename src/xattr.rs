@@ -0,0 +1,497 @@
+//! Encode and decode the Linux `posix_acl_xattr` binary format.
+//!
+//! The kernel stores a Posix.1e ACL in the `system.posix_acl_access` and
+//! `system.posix_acl_default` extended attributes. The value is a 4-byte
+//! version header followed by an array of 8-byte entries. All fields are
+//! little-endian.
+//!
+//! ```text
+//!   header: u32 version (2)
+//!   entry:  u16 tag, u16 perm, u32 id
+//! ```
+//!
+//! Use [`decode`] to convert an extended attribute's value into the same
+//! [`AclEntry`] values returned by [`getfacl`](crate::getfacl), and [`encode`]
+//! to go the other way. Neither function touches the file system.
+//! [`decode_with`] and [`encode_with`] take a
+//! [`PrincipalResolver`](crate::PrincipalResolver) to use instead of the
+//! system's user and group database. [`remap_ids`] maps the uid/gid in a
+//! value through an [`IdMap`](crate::IdMap), without any name lookups.
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use exacl::{xattr, AclEntry, AclOption, Perm};
+//!
+//! let entries = vec![
+//!     AclEntry::allow_user("", Perm::READ | Perm::WRITE, None),
+//!     AclEntry::allow_user("1000", Perm::READ, None),
+//!     AclEntry::allow_group("", Perm::READ, None),
+//!     AclEntry::allow_mask(Perm::READ, None),
+//!     AclEntry::allow_other(Perm::empty(), None),
+//! ];
+//!
+//! let value = xattr::encode(&entries, AclOption::NUMERIC_IDS)?;
+//! assert_eq!(xattr::decode(&value, AclOption::NUMERIC_IDS)?, entries);
+//! # Ok(()) }
+//! ```
+
+use crate::acl::AclOption;
+use crate::aclentry::AclEntry;
+use crate::error::Error;
+use crate::failx::fail;
+use crate::flag::Flag;
+use crate::idmap::IdMap;
+use crate::perm::Perm;
+use crate::qualifier::Qualifier;
+use crate::resolver::{self, NssResolver, PrincipalResolver};
+
+use std::io;
+
+/// Name of the extended attribute that holds the access ACL.
+pub const ACL_EA_ACCESS: &str = "system.posix_acl_access";
+
+/// Name of the extended attribute that holds the default ACL.
+pub const ACL_EA_DEFAULT: &str = "system.posix_acl_default";
+
+/// Format version stored in the header.
+const ACL_EA_VERSION: u32 = 0x0002;
+
+/// Size of the header in bytes.
+const HEADER_SIZE: usize = 4;
+
+/// Size of each entry in bytes.
+const ENTRY_SIZE: usize = 8;
+
+// Tag values used in the binary format.
+pub(crate) const ACL_USER_OBJ: u16 = 0x01;
+pub(crate) const ACL_USER: u16 = 0x02;
+pub(crate) const ACL_GROUP_OBJ: u16 = 0x04;
+pub(crate) const ACL_GROUP: u16 = 0x08;
+pub(crate) const ACL_MASK: u16 = 0x10;
+pub(crate) const ACL_OTHER: u16 = 0x20;
+
+/// Id value stored for entries that don't have a qualifier.
+pub(crate) const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+/// Decode the value of a `system.posix_acl_*` extended attribute.
+///
+/// Entries are returned in the order they are stored. If `options` contains
+/// [`AclOption::DEFAULT_ACL`], the entries are returned with the
+/// [`Flag::DEFAULT`] flag set. If `options` contains
+/// [`AclOption::NUMERIC_IDS`], users and groups are named by decimal uid/gid
+/// instead of being looked up, so decoding works for ids that are unknown to
+/// this host.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `data` is not a valid `posix_acl_xattr` value.
+pub fn decode<O>(data: &[u8], options: O) -> io::Result<Vec<AclEntry>>
+where
+    O: Into<Option<AclOption>>,
+{
+    decode_with(data, options, &NssResolver)
+}
+
+/// Decode the value of a `system.posix_acl_*` extended attribute, using
+/// `resolver` to convert uid/gid to user/group names.
+///
+/// Works like [`decode`], which uses [`NssResolver`](crate::NssResolver).
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `data` is not a valid `posix_acl_xattr` value.
+pub fn decode_with<O, R>(data: &[u8], options: O, resolver: &R) -> io::Result<Vec<AclEntry>>
+where
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let options = options.into().unwrap_or_default();
+    let resolver = resolver::for_options(options, &resolver);
+    let flags = if options.contains(AclOption::DEFAULT_ACL) {
+        Flag::DEFAULT
+    } else {
+        Flag::empty()
+    };
+
+    decode_entries(data)?
+        .into_iter()
+        .map(|entry| {
+            let perms = Perm::from_bits_truncate(u32::from(entry.perm));
+            AclEntry::from_qualifier(&entry.qualifier(), perms, flags, true, resolver)
+        })
+        .collect()
+}
+
+/// Encode entries as the value of a `system.posix_acl_*` extended attribute.
+///
+/// If `options` contains [`AclOption::DEFAULT_ACL`], only the entries with
+/// the [`Flag::DEFAULT`] flag are encoded. Otherwise, only entries without
+/// the flag are encoded. This lets you encode the access and default ACL from
+/// the same list returned by [`getfacl`](crate::getfacl). The entries are
+/// written in the order the kernel expects.
+///
+/// If `options` contains [`AclOption::NUMERIC_IDS`], user and group names
+/// must be decimal uid/gid; they are not looked up.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a name can't be resolved or an entry is not
+/// supported.
+pub fn encode<O>(entries: &[AclEntry], options: O) -> io::Result<Vec<u8>>
+where
+    O: Into<Option<AclOption>>,
+{
+    encode_with(entries, options, &NssResolver)
+}
+
+/// Encode entries as the value of a `system.posix_acl_*` extended attribute,
+/// using `resolver` to convert user/group names to uid/gid.
+///
+/// Works like [`encode`], which uses [`NssResolver`](crate::NssResolver).
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if a name can't be resolved or an entry is not
+/// supported.
+pub fn encode_with<O, R>(entries: &[AclEntry], options: O, resolver: &R) -> io::Result<Vec<u8>>
+where
+    O: Into<Option<AclOption>>,
+    R: PrincipalResolver + ?Sized,
+{
+    let options = options.into().unwrap_or_default();
+    let resolver = resolver::for_options(options, &resolver);
+    let default_acl = options.contains(AclOption::DEFAULT_ACL);
+
+    let mut result = Vec::<XattrEntry>::with_capacity(entries.len());
+    for entry in entries {
+        if entry.flags.contains(Flag::DEFAULT) != default_acl {
+            continue;
+        }
+
+        if !entry.allow {
+            return fail(Error::NotSupported(
+                "allow=false is not supported on Linux".to_string(),
+            ));
+        }
+
+        if !(entry.flags - Flag::DEFAULT).is_empty() {
+            return fail(Error::InvalidEntry(format!(
+                "unsupported flags: {}",
+                entry.flags
+            )));
+        }
+
+        let qualifier = entry.qualifier(resolver)?;
+        let xentry = XattrEntry::new(&qualifier, entry.perms)?;
+
+        let Err(pos) = result.binary_search_by_key(&xentry.sort_key(), |e| e.sort_key()) else {
+            return fail(Error::DuplicateEntry {
+                entry: qualifier.to_string(),
+                default: default_acl,
+            });
+        };
+        result.insert(pos, xentry);
+    }
+
+    Ok(encode_entries(&result))
+}
+
+/// Remap the uid/gid of named user and group entries in the value of a
+/// `system.posix_acl_*` extended attribute.
+///
+/// Ids are mapped from outside to inside using `map`, like
+/// [`remap_ids`](crate::remap_ids), but names are never looked up. The
+/// entries are written back in the order the kernel expects.
+///
+/// # Errors
+///
+/// Returns an [`io::Error`] if `data` is not a valid `posix_acl_xattr` value,
+/// or an id isn't mapped.
+pub fn remap_ids(data: &[u8], map: &IdMap) -> io::Result<Vec<u8>> {
+    let mut entries = decode_entries(data)?;
+
+    for entry in &mut entries {
+        let mapped = match entry.tag {
+            ACL_USER => map.map_uid(entry.id).ok_or("uid"),
+            ACL_GROUP => map.map_gid(entry.id).ok_or("gid"),
+            _ => continue,
+        };
+        match mapped {
+            Ok(id) => entry.id = id,
+            Err(kind) => {
+                return fail(Error::InvalidEntry(format!(
+                    "unmapped {kind}: {}",
+                    entry.id
+                )))
+            }
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.sort_key());
+    Ok(encode_entries(&entries))
+}
+
+/// Entry in the binary format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct XattrEntry {
+    pub tag: u16,
+    pub perm: u16,
+    pub id: u32,
+}
+
+impl XattrEntry {
+    /// Construct an entry from a qualifier and permissions.
+    pub fn new(qualifier: &Qualifier, perms: Perm) -> io::Result<XattrEntry> {
+        let (tag, id) = match qualifier {
+            Qualifier::User(uid) => (ACL_USER, *uid),
+            Qualifier::Group(gid) => (ACL_GROUP, *gid),
+            Qualifier::UserObj => (ACL_USER_OBJ, ACL_UNDEFINED_ID),
+            Qualifier::GroupObj => (ACL_GROUP_OBJ, ACL_UNDEFINED_ID),
+            Qualifier::Other => (ACL_OTHER, ACL_UNDEFINED_ID),
+            Qualifier::Mask => (ACL_MASK, ACL_UNDEFINED_ID),
+            Qualifier::Unknown(tag) => {
+                return fail(Error::InvalidEntry(format!("unknown tag: {tag}")));
+            }
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let perm = (perms & Perm::all()).bits() as u16;
+
+        Ok(XattrEntry { tag, perm, id })
+    }
+
+    /// Return the qualifier for the entry.
+    pub fn qualifier(self) -> Qualifier {
+        match self.tag {
+            ACL_USER => Qualifier::User(self.id),
+            ACL_GROUP => Qualifier::Group(self.id),
+            ACL_USER_OBJ => Qualifier::UserObj,
+            ACL_GROUP_OBJ => Qualifier::GroupObj,
+            ACL_OTHER => Qualifier::Other,
+            ACL_MASK => Qualifier::Mask,
+            tag => Qualifier::Unknown(format!("@tag {tag}")),
+        }
+    }
+
+    /// Return sort key used to put entries in the order the kernel expects.
+    pub const fn sort_key(self) -> (u16, u32) {
+        (self.tag, self.id)
+    }
+}
+
+/// Encode entries into the binary format.
+///
+/// Entries are written in the order given. The kernel requires the entries
+/// to be sorted by tag, then id; the caller is responsible for this.
+pub(crate) fn encode_entries(entries: &[XattrEntry]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_SIZE + ENTRY_SIZE * entries.len());
+
+    buf.extend_from_slice(&ACL_EA_VERSION.to_le_bytes());
+    for entry in entries {
+        buf.extend_from_slice(&entry.tag.to_le_bytes());
+        buf.extend_from_slice(&entry.perm.to_le_bytes());
+        buf.extend_from_slice(&entry.id.to_le_bytes());
+    }
+
+    buf
+}
+
+/// Decode entries from the binary format.
+pub(crate) fn decode_entries(data: &[u8]) -> io::Result<Vec<XattrEntry>> {
+    if data.len() < HEADER_SIZE || !(data.len() - HEADER_SIZE).is_multiple_of(ENTRY_SIZE) {
+        return fail(Error::InvalidInput(format!(
+            "invalid posix_acl_xattr size: {}",
+            data.len()
+        )));
+    }
+
+    let version = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    if version != ACL_EA_VERSION {
+        return fail(Error::NotSupported(format!(
+            "unsupported posix_acl_xattr version: {version}"
+        )));
+    }
+
+    let entries = data[HEADER_SIZE..]
+        .chunks_exact(ENTRY_SIZE)
+        .map(|chunk| XattrEntry {
+            tag: u16::from_le_bytes([chunk[0], chunk[1]]),
+            perm: u16::from_le_bytes([chunk[2], chunk[3]]),
+            id: u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod xattr_tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let entries = vec![
+            XattrEntry {
+                tag: ACL_USER_OBJ,
+                perm: 6,
+                id: ACL_UNDEFINED_ID,
+            },
+            XattrEntry {
+                tag: ACL_USER,
+                perm: 4,
+                id: 1000,
+            },
+            XattrEntry {
+                tag: ACL_GROUP_OBJ,
+                perm: 4,
+                id: ACL_UNDEFINED_ID,
+            },
+            XattrEntry {
+                tag: ACL_MASK,
+                perm: 4,
+                id: ACL_UNDEFINED_ID,
+            },
+            XattrEntry {
+                tag: ACL_OTHER,
+                perm: 0,
+                id: ACL_UNDEFINED_ID,
+            },
+        ];
+
+        let data = encode_entries(&entries);
+        assert_eq!(data.len(), 44);
+        assert_eq!(&data[0..12], &[2, 0, 0, 0, 1, 0, 6, 0, 255, 255, 255, 255]);
+        assert_eq!(&data[12..20], &[2, 0, 4, 0, 0xe8, 0x03, 0, 0]);
+
+        assert_eq!(decode_entries(&data).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let err = decode_entries(&[]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid posix_acl_xattr size: 0");
+
+        let err = decode_entries(&[2, 0, 0, 0, 1]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid posix_acl_xattr size: 5");
+
+        let err = decode_entries(&[1, 0, 0, 0]).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported posix_acl_xattr version: 1");
+
+        assert!(decode_entries(&[2, 0, 0, 0]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = [
+            2, 0, 0, 0, // version
+            1, 0, 6, 0, 255, 255, 255, 255, // user::rw-
+            2, 0, 4, 0, 0x39, 0x30, 0, 0, // user:12345:r--
+            4, 0, 4, 0, 255, 255, 255, 255, // group::r--
+            0x40, 0, 1, 0, 255, 255, 255, 255, // unknown tag
+        ];
+
+        let entries = decode(&data, AclOption::NUMERIC_IDS).unwrap();
+        let text = entries
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            text,
+            "allow::user::read,write\nallow::user:12345:read\nallow::group::read\nallow::unknown:@tag\\04064:execute"
+        );
+
+        let entries = decode(&data[..20], AclOption::DEFAULT_ACL | AclOption::NUMERIC_IDS).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                AclEntry::allow_user("", Perm::READ | Perm::WRITE, Flag::DEFAULT),
+                AclEntry::allow_user("12345", Perm::READ, Flag::DEFAULT),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let entries = vec![
+            AclEntry::allow_other(Perm::empty(), None),
+            AclEntry::allow_user("500", Perm::EXECUTE, None),
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_group("", Perm::READ, Flag::DEFAULT),
+        ];
+
+        let data_numeric = encode(&entries, AclOption::NUMERIC_IDS).unwrap();
+        assert_eq!(
+            decode_entries(&data_numeric).unwrap(),
+            vec![
+                XattrEntry {
+                    tag: ACL_USER_OBJ,
+                    perm: 4,
+                    id: ACL_UNDEFINED_ID
+                },
+                XattrEntry {
+                    tag: ACL_USER,
+                    perm: 1,
+                    id: 500
+                },
+                XattrEntry {
+                    tag: ACL_OTHER,
+                    perm: 0,
+                    id: ACL_UNDEFINED_ID
+                },
+            ]
+        );
+
+        let data = encode(&entries, AclOption::DEFAULT_ACL).unwrap();
+        assert_eq!(data, [2, 0, 0, 0, 4, 0, 4, 0, 255, 255, 255, 255]);
+
+        // Without NUMERIC_IDS, a decimal name that isn't found is used as is.
+        assert_eq!(encode(&entries, None).unwrap(), data_numeric);
+
+        let dup = vec![
+            AclEntry::allow_user("500", Perm::READ, None),
+            AclEntry::allow_user("500", Perm::WRITE, None),
+        ];
+        let err = encode(&dup, AclOption::NUMERIC_IDS).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate entry for \"user:500\"");
+
+        let named = vec![AclEntry::allow_user("non_existant_user", Perm::READ, None)];
+        let err = encode(&named, AclOption::NUMERIC_IDS).unwrap_err();
+        assert_eq!(err.to_string(), "invalid user id: \"non_existant_user\"");
+    }
+
+    #[test]
+    fn test_remap_ids() {
+        let mut map = IdMap::new();
+        map.add_uids(0, 100_000, 1000).unwrap();
+        map.add_uids(1000, 1000, 1).unwrap();
+        map.add_gids(0, 100_000, 1000).unwrap();
+
+        let entries = vec![
+            AclEntry::allow_user("", Perm::READ, None),
+            AclEntry::allow_user("1000", Perm::READ, None),
+            AclEntry::allow_user("100001", Perm::WRITE, None),
+            AclEntry::allow_group("100002", Perm::READ, None),
+            AclEntry::allow_other(Perm::empty(), None),
+        ];
+        let data = encode(&entries, AclOption::NUMERIC_IDS).unwrap();
+
+        let remapped = remap_ids(&data, &map).unwrap();
+        assert_eq!(
+            decode(&remapped, AclOption::NUMERIC_IDS).unwrap(),
+            vec![
+                AclEntry::allow_user("", Perm::READ, None),
+                AclEntry::allow_user("1", Perm::WRITE, None),
+                AclEntry::allow_user("1000", Perm::READ, None),
+                AclEntry::allow_group("2", Perm::READ, None),
+                AclEntry::allow_other(Perm::empty(), None),
+            ]
+        );
+        assert_eq!(remap_ids(&remapped, &map.reverse()).unwrap(), data);
+
+        let err = remap_ids(&remapped, &map).unwrap_err();
+        assert_eq!(err.to_string(), "unmapped uid: 1");
+    }
+}
@@ -1,7 +1,10 @@
 //! API Tests for exacl module.
 
 use ctor::ctor;
-use exacl::{getfacl, setfacl, AclEntry, AclOption, Perm};
+use exacl::{
+    getfacl, getfacl_fd, getfacl_recursive, setfacl, setfacl_fd, setfacl_recursive, AclEntry,
+    AclOption, Error, Perm,
+};
 use log::debug;
 use std::io;
 
@@ -70,6 +73,87 @@ fn test_setfacl_file() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_setfacl_fd() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+    let mut entries = getfacl_fd(file.as_file(), None)?;
+    assert_eq!(entries, getfacl(&file, None)?);
+
+    entries.push(AclEntry::allow_user("500", Perm::READ, None));
+    setfacl_fd(file.as_file(), &entries, None)?;
+
+    let entries2 = getfacl(&file, None)?;
+    assert!(entries2.contains(&AclEntry::allow_user("500", Perm::READ, None)));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_setfacl_fd_default() -> io::Result<()> {
+    use exacl::Flag;
+
+    let dir = tempfile::tempdir()?;
+    let dir_file = std::fs::File::open(&dir)?;
+
+    let mut entries = exacl::from_mode(0o750);
+    let mut default_entries = exacl::from_mode(0o700);
+    for entry in &mut default_entries {
+        entry.flags |= Flag::DEFAULT;
+    }
+    entries.append(&mut default_entries);
+
+    setfacl_fd(&dir_file, &entries, None)?;
+    assert_eq!(exacl::to_string(&getfacl_fd(&dir_file, None)?)?, "allow::user::read,write,execute\nallow::group::read,execute\nallow::other::\nallow:default:user::read,write,execute\nallow:default:group::\nallow:default:other::\n");
+
+    // Default entries for a regular file are an error.
+    let file = tempfile::NamedTempFile::new()?;
+    let err = setfacl_fd(file.as_file(), &entries, None).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("Non-directory does not have default ACL"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_setfacl_recursive() -> io::Result<()> {
+    use exacl::{Flag, RecursiveOption};
+
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir(dir.path().join("subdir"))?;
+    std::fs::write(dir.path().join("subdir/file"), "")?;
+
+    let mut entries = exacl::from_mode(0o750);
+    let mut default_entries = exacl::from_mode(0o700);
+    for entry in &mut default_entries {
+        entry.flags |= Flag::DEFAULT;
+    }
+    entries.append(&mut default_entries);
+
+    // Default entries for a regular file are an error.
+    let err = setfacl_recursive(&dir, &entries, None).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("Non-directory does not have default ACL"));
+
+    setfacl_recursive(&dir, &entries, RecursiveOption::DEFAULT_DIRS_ONLY)?;
+
+    let results = getfacl_recursive(&dir, None)
+        .map(|(path, result)| (path, result.unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0], (dir.path().to_path_buf(), entries.clone()));
+    assert_eq!(results[1], (dir.path().join("subdir"), entries.clone()));
+    assert_eq!(
+        results[2],
+        (dir.path().join("subdir/file"), exacl::from_mode(0o750))
+    );
+
+    Ok(())
+}
+
 #[test]
 #[cfg(target_os = "linux")]
 fn test_too_many_entries() -> io::Result<()> {
@@ -142,6 +226,64 @@ fn test_exclusive_acloptions() {
     );
 }
 
+#[test]
+fn test_typed_errors() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+
+    let entries = vec![AclEntry::allow_user("non_existant_user", Perm::READ, None)];
+    let err = setfacl(&[&file], &entries, None).unwrap_err();
+    assert_eq!(
+        Error::from_io(&err),
+        Some(&Error::UnknownUser("non_existant_user".to_string()))
+    );
+
+    let err = getfacl("/non/existant/path", None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(Error::errno(&err), Some(2));
+    match Error::from_io(&err) {
+        Some(Error::Os { path, .. }) => {
+            assert_eq!(
+                path.as_deref(),
+                Some(std::path::Path::new("/non/existant/path"))
+            );
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+
+    let err = exacl::from_str("allow::user::read\n  allow:foo").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 2, column 3: Unknown ACL format: `allow:foo`"
+    );
+    assert!(matches!(
+        Error::from_io(&err),
+        Some(Error::Parse {
+            line: 2,
+            column: 3,
+            ..
+        })
+    ));
+
+    let report = exacl::parse_reader(&b"u::rw\n  g:admin:rx,wrte\nfoo\no::r # bad\n"[..])?;
+    assert_eq!(report.entries.len(), 2);
+    let errors = report.into_result().unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        errors[0],
+        Error::Parse {
+            line: 2,
+            column: 14,
+            ..
+        }
+    ));
+    assert_eq!(
+        errors[1].to_string(),
+        "line 3, column 1: Unknown ACL format: `foo`"
+    );
+
+    Ok(())
+}
+
 #[test]
 #[cfg(target_os = "macos")]
 fn test_exclusive_acloptions() {
@@ -181,3 +323,451 @@ fn test_from_mode() {
         "allow::user::write,execute\nallow::group::read\nallow::other::read,execute\n"
     );
 }
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_inherit_acl() -> io::Result<()> {
+    let parent = exacl::from_str(
+        "allow::user::read,write,execute
+        allow::group::read,execute
+        allow::other::read
+        allow:default:user::read,write,execute
+        allow:default:user:500:read,write
+        allow:default:group::read,execute
+        allow:default:other::read",
+    )?;
+
+    // Mask is computed for the default ACL, then limited by mode.
+    let file_acl = exacl::inherit_acl(&parent, 0o666, 0o022, false)?;
+    assert_eq!(
+        exacl::to_string(&file_acl)?,
+        "allow::user::read,write\nallow::user:500:read,write\nallow::group::read,execute\nallow::other::read\nallow::mask::read,write\n"
+    );
+
+    let dir_acl = exacl::inherit_acl(&parent, 0o750, 0o777, true)?;
+    assert_eq!(
+        exacl::to_string(&dir_acl)?,
+        "allow::user::read,write,execute\nallow::user:500:read,write\nallow::group::read,execute\nallow::other::\nallow::mask::read,execute\nallow:default:user::read,write,execute\nallow:default:user:500:read,write\nallow:default:group::read,execute\nallow:default:other::read\nallow:default:mask::read,write,execute\n"
+    );
+
+    // Without a default ACL, the umask applies.
+    let file_acl = exacl::inherit_acl(&parent[0..3], 0o666, 0o027, false)?;
+    assert_eq!(file_acl, exacl::from_mode(0o640));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_inherit_acl_matches_kernel() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut entries = exacl::from_str(
+        "u::rwx
+        g::rx
+        o::
+        d:u::rwx
+        d:u:500:rw
+        d:g::r
+        d:m::rx
+        d:o::r",
+    )?;
+    setfacl(&[&dir], &entries, None)?;
+    entries = getfacl(&dir, None)?;
+
+    // The umask is ignored when the parent has a default ACL.
+    let file = dir.path().join("file");
+    std::fs::File::create(&file)?;
+    let expected = exacl::inherit_acl(&entries, 0o666, 0o777, false)?;
+    assert_eq!(getfacl(&file, None)?, expected);
+
+    let subdir = dir.path().join("subdir");
+    std::fs::create_dir(&subdir)?;
+    let expected = exacl::inherit_acl(&entries, 0o777, 0o777, true)?;
+    assert_eq!(getfacl(&subdir, None)?, expected);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_apply_mode() -> io::Result<()> {
+    let entries = exacl::from_str("u::rwx\nu:500:rwx\ng::rx\nm::rwx\no::r\nd:m::rwx")?;
+
+    // Group bits update the mask, not the owning group.
+    let result = exacl::apply_mode(&entries, 0o640);
+    assert_eq!(
+        exacl::to_string(&result)?,
+        "allow::user::read,write\nallow::user:500:read,write,execute\nallow::group::read,execute\nallow::mask::read\nallow::other::\nallow:default:mask::read,write,execute\n"
+    );
+    assert_eq!(exacl::to_mode(&result), 0o640);
+
+    // Without a mask, group bits update the owning group.
+    let result = exacl::apply_mode(&exacl::from_mode(0o777), 0o751);
+    assert_eq!(result, exacl::from_mode(0o751));
+    assert_eq!(exacl::to_mode(&result), 0o751);
+
+    // A mask is added when needed.
+    let result = exacl::apply_mode(&entries[0..3], 0o700);
+    assert_eq!(
+        exacl::to_string(&result)?,
+        "allow::user::read,write,execute\nallow::user:500:read,write,execute\nallow::group::read,execute\nallow::mask::\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_chmod_with_acl() -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = tempfile::NamedTempFile::new()?;
+    let entries = exacl::from_str("u::rw\nu:500:rw\ng::r\no::")?;
+    setfacl(&[&file], &entries, None)?;
+
+    exacl::chmod_with_acl(&file, 0o4750)?;
+    assert_eq!(
+        exacl::to_string(&getfacl(&file, None)?)?,
+        "allow::user::read,write,execute\nallow::user:500:read,write\nallow::group::read\nallow::mask::read,execute\nallow::other::\n"
+    );
+
+    let mode = std::fs::metadata(&file)?.permissions().mode();
+    assert_eq!(mode & 0o7777, 0o4750);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_remove_extended_acl() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let entries = exacl::from_str("u::rwx\nu:500:rwx\ng::r\nm::rwx\no::\nd:u::rwx\nd:g::\nd:o::")?;
+    setfacl(&[&dir], &entries, None)?;
+
+    // Owning group keeps its own permissions, limited by the mask.
+    exacl::remove_extended_acl(&dir)?;
+    assert_eq!(
+        exacl::to_string(&getfacl(&dir, None)?)?,
+        "allow::user::read,write,execute\nallow::group::read\nallow::other::\nallow:default:user::read,write,execute\nallow:default:group::\nallow:default:other::\n"
+    );
+
+    exacl::remove_default_acl(&dir)?;
+    assert_eq!(getfacl(&dir, None)?, exacl::from_mode(0o740));
+
+    // Removing the default ACL of a file does nothing.
+    let file = tempfile::NamedTempFile::new()?;
+    exacl::remove_default_acl(&file)?;
+
+    // A mask narrower than the owning group limits the group's permissions.
+    let entries = exacl::from_str("u::rw\nu:500:rw\ng::rwx\nm::r\no::")?;
+    setfacl(&[&file], &entries, None)?;
+    exacl::remove_extended_acl(&file)?;
+    assert_eq!(getfacl(&file, None)?, exacl::from_mode(0o640));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_modify_acl() -> io::Result<()> {
+    use exacl::{Flag, Principal};
+
+    let dir = tempfile::tempdir()?;
+    let entries = exacl::from_str("u::rwx\nu:500:r\ng::r\nm::r\no::\nd:u::rwx\nd:g::\nd:o::")?;
+    setfacl(&[&dir], &entries, None)?;
+
+    // Update user 500, add user 501 and a default entry. Mask is recomputed.
+    let changes = exacl::from_str("u:500:rw\nu:501:x\nd:u:500:rx")?;
+    exacl::modify_acl(&dir, &changes, None)?;
+    assert_eq!(
+        exacl::to_string(&getfacl(&dir, None)?)?,
+        "allow::user::read,write,execute\nallow::user:500:read,write\nallow::user:501:execute\nallow::group::read\nallow::mask::read,write,execute\nallow::other::\nallow:default:user::read,write,execute\nallow:default:user:500:read,execute\nallow:default:group::\nallow:default:mask::read,execute\nallow:default:other::\n"
+    );
+
+    // An explicit mask is kept.
+    let changes = exacl::from_str("u:500:rwx\nm::r")?;
+    exacl::modify_acl(&dir, &changes, AclOption::ACCESS_ACL)?;
+    let access = getfacl(&dir, AclOption::ACCESS_ACL)?;
+    assert!(access.contains(&AclEntry::allow_mask(Perm::READ, None)));
+
+    // Remove user 501 and the default entry for user 500.
+    let principals = [
+        Principal::user("501", None),
+        Principal::user("500", Flag::DEFAULT),
+    ];
+    exacl::remove_acl_entries(&dir, &principals, None)?;
+    assert_eq!(
+        exacl::to_string(&getfacl(&dir, None)?)?,
+        "allow::user::read,write,execute\nallow::user:500:read,write,execute\nallow::group::read\nallow::mask::read,write,execute\nallow::other::\nallow:default:user::read,write,execute\nallow:default:group::\nallow:default:other::\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_numeric_ids() -> io::Result<()> {
+    let file = tempfile::NamedTempFile::new()?;
+
+    let entries = exacl::from_str("u::rw\nu:root:r\ng::r\no::")?;
+    setfacl(&[&file], &entries, None)?;
+    assert_eq!(getfacl(&file, None)?[1].name, "root");
+    assert_eq!(getfacl(&file, AclOption::NUMERIC_IDS)?[1].name, "0");
+
+    // Names are not looked up when writing, either.
+    let err = setfacl(&[&file], &entries, AclOption::NUMERIC_IDS).unwrap_err();
+    assert!(err.to_string().contains("root"), "{err}");
+
+    let entries = exacl::from_str("u::rw\nu:4294967:r\ng::r\no::")?;
+    setfacl(&[&file], &entries, AclOption::NUMERIC_IDS)?;
+    assert_eq!(getfacl(&file, AclOption::NUMERIC_IDS)?[1].name, "4294967");
+
+    // Entries keep their uid/gid, and entries with an id are not looked up.
+    let mut entries = getfacl(&file, None)?;
+    assert_eq!(entries[1].id, Some(4_294_967));
+    entries[1] = AclEntry::allow_uid(0, Perm::WRITE, None);
+    setfacl(&[&file], &entries, AclOption::NUMERIC_IDS)?;
+    assert_eq!(
+        getfacl(&file, None)?[1],
+        AclEntry::allow_user("root", Perm::WRITE, None)
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_resolver() -> io::Result<()> {
+    use exacl::{
+        getfacl_fd_with, getfacl_with, modify_acl_with, normalize_with, remap_ids_with,
+        remove_acl_entries_with, setfacl_with, validate_with, CachingResolver, IdMap, Principal,
+        PrincipalResolver,
+    };
+
+    /// Resolver with one user, "alice", and no groups.
+    struct Fixed;
+
+    impl PrincipalResolver for Fixed {
+        fn name_to_uid(&self, name: &str) -> io::Result<u32> {
+            match name {
+                "alice" => Ok(4242),
+                _ => Err(Error::UnknownUser(name.to_string()).into()),
+            }
+        }
+
+        fn uid_to_name(&self, uid: u32) -> io::Result<String> {
+            match uid {
+                4242 => Ok("alice".to_string()),
+                _ => Ok(uid.to_string()),
+            }
+        }
+
+        fn name_to_gid(&self, name: &str) -> io::Result<u32> {
+            Err(Error::UnknownGroup(name.to_string()).into())
+        }
+
+        fn gid_to_name(&self, gid: u32) -> io::Result<String> {
+            Ok(gid.to_string())
+        }
+    }
+
+    let file = tempfile::NamedTempFile::new()?;
+    let resolver = CachingResolver::new(Fixed);
+
+    let entries = exacl::from_str("u::rw\nu:alice:r\ng::r\no::")?;
+    setfacl_with(&[&file], &entries, None, &resolver)?;
+    assert_eq!(getfacl(&file, AclOption::NUMERIC_IDS)?[1].name, "4242");
+    assert_eq!(getfacl_with(&file, None, &resolver)?[1].name, "alice");
+
+    let entries = exacl::from_str("u::rw\ng:staff:r\ng::r\no::")?;
+    let err = setfacl_with(&[&file], &entries, None, &resolver).unwrap_err();
+    assert_eq!(
+        Error::from_io(&err),
+        Some(&Error::UnknownGroup("staff".to_string()))
+    );
+
+    // Functions that look up names have a `_with` variant.
+    assert_eq!(
+        getfacl_fd_with(file.as_file(), None, &resolver)?[1].name,
+        "alice"
+    );
+
+    let alice = exacl::from_str("u:alice:rw")?;
+    modify_acl_with(&file, &alice, None, &resolver)?;
+    assert_eq!(
+        getfacl(&file, AclOption::NUMERIC_IDS)?[1].perms,
+        Perm::READ | Perm::WRITE
+    );
+
+    let entries = getfacl_with(&file, None, &resolver)?;
+    assert_eq!(validate_with(&entries, None, &resolver), Ok(()));
+    assert_eq!(normalize_with(&entries, &resolver)?, entries);
+
+    let mut map = IdMap::new();
+    map.add_uids(0, 4242, 1)?;
+    assert_eq!(remap_ids_with(&alice, &map, &resolver)?[0].name, "0");
+
+    remove_acl_entries_with(&file, &[Principal::user("alice", None)], None, &resolver)?;
+    assert_eq!(getfacl(&file, None)?.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_posix_text() -> io::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+
+    let entries = exacl::from_posix_text(
+        "user::rwx\nuser:500:rw-\ngroup::r-x\nmask::r--\nother::---\ndefault:user::rwx\ndefault:group::r-x\ndefault:other::---\n",
+    )?;
+    setfacl(&[&dir], &entries, None)?;
+
+    assert_eq!(
+        exacl::to_posix_text(&getfacl(&dir, None)?, exacl::TextOption::EFFECTIVE)?,
+        "user::rwx\nuser:500:rw-\t\t\t#effective:r--\ngroup::r-x\t\t\t#effective:r--\nmask::r--\nother::---\ndefault:user::rwx\ndefault:group::r-x\ndefault:other::---\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_dump_restore() -> io::Result<()> {
+    use exacl::RecursiveOption;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let dir = tempfile::TempDir::new()?;
+    let sub = dir.path().join("a b");
+    std::fs::create_dir(&sub)?;
+    std::fs::write(sub.join("f"), "")?;
+
+    let entries =
+        exacl::from_posix_text("u::rwx\nu:500:r-x\ng::r-x\no::\nd:u::rwx\nd:g::r-x\nd:o::")?;
+    setfacl(&[&sub], &entries, None)?;
+    std::fs::set_permissions(&sub, std::fs::Permissions::from_mode(0o3750))?;
+
+    let mut dump = Vec::new();
+    exacl::dump_tree(dir.path(), &mut dump, None)?;
+    let dump = String::from_utf8(dump).unwrap();
+    assert!(dump.contains("# file: a\\040b\n"));
+    assert!(dump.contains("# flags: -st\nuser::rwx\nuser:500:r-x\n"));
+
+    // Clear the ACL and flags, then restore them.
+    exacl::remove_extended_acl(&sub)?;
+    exacl::remove_default_acl(&sub)?;
+    std::fs::set_permissions(&sub, std::fs::Permissions::from_mode(0o700))?;
+    exacl::restore(dump.as_bytes(), dir.path(), None)?;
+
+    let mut restored = Vec::new();
+    exacl::dump_tree(dir.path(), &mut restored, None)?;
+    assert_eq!(String::from_utf8(restored).unwrap(), dump);
+
+    // A numeric dump records ids, and restores without looking up names.
+    let mut numeric = Vec::new();
+    exacl::dump_tree(dir.path(), &mut numeric, RecursiveOption::NUMERIC_IDS)?;
+    let numeric = String::from_utf8(numeric).unwrap();
+    let uid = std::fs::metadata(dir.path())?.uid();
+    assert!(numeric.starts_with(&format!("# file: .\n# owner: {uid}\n")));
+    exacl::restore(numeric.as_bytes(), dir.path(), RecursiveOption::NUMERIC_IDS)?;
+
+    // Owner names are not looked up.
+    assert!(exacl::restore(dump.as_bytes(), dir.path(), RecursiveOption::NUMERIC_IDS).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_diff_apply() -> io::Result<()> {
+    let file1 = tempfile::NamedTempFile::new()?;
+    let file2 = tempfile::NamedTempFile::new()?;
+    setfacl(
+        &[&file1],
+        &exacl::from_str("u::rw\ng::r\no::\nu:500:r\nm::r")?,
+        None,
+    )?;
+    setfacl(
+        &[&file2],
+        &exacl::from_str("u::rw\ng::r\no::\nu:500:rw\nm::rw")?,
+        None,
+    )?;
+
+    // Compute the change to one file, and replay it onto the other.
+    let old = getfacl(&file1, None)?;
+    let mut new = old.clone();
+    new.push(AclEntry::allow_group("600", Perm::READ, None));
+    new.retain(|entry| entry.name != "500");
+    let changes = exacl::diff(&old, &new);
+    assert_eq!(changes.added.len(), 1);
+    assert_eq!(changes.removed.len(), 1);
+
+    #[cfg(feature = "serde")]
+    let changes: exacl::AclDiff = {
+        let json = serde_json::to_string(&changes)?;
+        serde_json::from_str(&json)?
+    };
+
+    let entries = exacl::apply(&getfacl(&file2, None)?, &changes);
+    setfacl(&[&file2], &entries, None)?;
+    assert_eq!(
+        exacl::to_string(&getfacl(&file2, None)?)?,
+        "allow::user::read,write\nallow::group::read\nallow::group:600:read\nallow::mask::read,write\nallow::other::\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn test_document() -> io::Result<()> {
+    use exacl::{AclDocument, FileKind};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir1 = tempfile::TempDir::new()?;
+    let dir2 = tempfile::TempDir::new()?;
+    let entries = exacl::from_str("u::rwx\ng::rx\no::\nu:500:r\nm::rx\nd:u::rwx\nd:g::r\nd:o::")?;
+    setfacl(&[&dir1], &entries, None)?;
+    std::fs::set_permissions(&dir1, std::fs::Permissions::from_mode(0o2750))?;
+
+    let doc = AclDocument::read(&dir1, AclOption::NUMERIC_IDS)?;
+    assert_eq!(doc.version, AclDocument::VERSION);
+    assert_eq!(doc.path, dir1.path());
+    assert_eq!(doc.mode, 0o2750);
+    assert_eq!(doc.file_type, FileKind::Directory);
+    assert_eq!(
+        exacl::to_string(&doc.access)?,
+        "allow::user::read,write,execute\nallow::user:500:read\nallow::group::read,execute\nallow::mask::read,execute\nallow::other::\n"
+    );
+    assert_eq!(
+        exacl::to_string(&doc.default)?,
+        "allow::user::read,write,execute\nallow::group::read\nallow::other::\n"
+    );
+
+    #[cfg(feature = "serde")]
+    let doc: AclDocument = {
+        let json = serde_json::to_string(&doc)?;
+        assert!(json.starts_with("{\"version\":1,\"path\":"), "{json}");
+        serde_json::from_str(&json)?
+    };
+
+    doc.apply(&dir2, AclOption::NUMERIC_IDS)?;
+    let copy = AclDocument::read(&dir2, AclOption::NUMERIC_IDS)?;
+    assert_eq!(copy.path, dir2.path());
+    assert_eq!(
+        AclDocument {
+            path: doc.path.clone(),
+            ..copy
+        },
+        doc
+    );
+
+    let future = AclDocument {
+        version: AclDocument::VERSION + 1,
+        ..doc
+    };
+    let err = future.apply(&dir2, None).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported document version: 2");
+
+    Ok(())
+}
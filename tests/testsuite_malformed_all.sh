@@ -233,7 +233,7 @@ testInvalidStdFormat() {
     msg=$(echo "$input" | $EXACL -f std --set non_existant 2>&1)
     assertEquals 1 $?
     assertEquals \
-        "Std parser error: Unknown ACL format: user:x" \
+        "Std parser error: line 2, column 1: Unknown ACL format: user:x" \
         "${msg//\`/}"
 }
 